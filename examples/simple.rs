@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
 use image::GenericImageView;
-use spright::Renderer;
+use spright::{PreparedBatch, Renderer};
 use wgpu::{
     util::DeviceExt, Adapter, CreateSurfaceError, Device, DeviceDescriptor, PresentMode, Queue,
     RenderPass, Surface, SurfaceConfiguration,
@@ -38,6 +38,7 @@ impl Graphics {
 
 struct Inner {
     spright_renderer: Renderer,
+    batch: PreparedBatch,
     texture1: wgpu::Texture,
     texture2: wgpu::Texture,
 }
@@ -77,6 +78,7 @@ impl Inner {
             gfx.surface.get_capabilities(&gfx.adapter).formats[0],
         );
         Self {
+            batch: spright_renderer.create_batch(&gfx.device),
             spright_renderer,
             texture1: load_texture(
                 &gfx.device,
@@ -92,62 +94,55 @@ impl Inner {
     }
 
     pub fn prepare(&mut self, device: &Device, queue: &Queue, target_size: wgpu::Extent3d) {
-        self.spright_renderer.prepare(
+        self.spright_renderer.prepare_into(
             device,
             queue,
+            &mut self.batch,
             target_size,
             &[
-                spright::Group {
-                    texture: &self.texture1,
-                    items: vec![
+                spright::Group::new(
+                    &self.texture1,
+                    vec![
                         spright::Item {
-                            src_offset: glam::ivec2(0, 0),
                             src_size: glam::uvec2(280 / 2, 210 / 2),
-                            src_layer: 0,
-                            transform: glam::Affine2::IDENTITY,
-                            tint: spright::Color::new(0xff, 0xff, 0xff, 0xff),
+                            ..Default::default()
                         },
                         spright::Item {
-                            src_offset: glam::ivec2(0, 0),
                             src_size: glam::uvec2(280, 210),
-                            src_layer: 0,
                             transform: glam::Affine2::from_translation(glam::vec2(100.0, 100.0)),
-                            tint: spright::Color::new(0xff, 0xff, 0xff, 0xff),
+                            ..Default::default()
                         },
                     ],
-                },
-                spright::Group {
-                    texture: &self.texture2,
-                    items: vec![spright::Item {
-                        src_offset: glam::ivec2(0, 0),
+                ),
+                spright::Group::new(
+                    &self.texture2,
+                    vec![spright::Item {
                         src_size: glam::uvec2(386, 395),
-                        src_layer: 0,
                         transform: glam::Affine2::from_scale(glam::Vec2::new(2.0, 3.0))
                             * glam::Affine2::from_translation(glam::Vec2::new(200.0, 0.0)),
-                        tint: spright::Color::new(0xff, 0xff, 0xff, 0xff),
+                        ..Default::default()
                     }],
-                },
-                spright::Group {
-                    texture: &self.texture1,
-                    items: vec![spright::Item {
-                        src_offset: glam::ivec2(0, 0),
+                ),
+                spright::Group::new(
+                    &self.texture1,
+                    vec![spright::Item {
                         src_size: glam::uvec2(280, 210),
-                        src_layer: 0,
                         transform: glam::Affine2::from_translation(glam::Vec2::new(
                             140.0 * 3.0,
                             105.0 * 3.0,
                         )) * glam::Affine2::from_angle(1.0)
                             * glam::Affine2::from_scale(glam::Vec2::new(3.0, 3.0))
                             * glam::Affine2::from_translation(glam::Vec2::new(-140.0, -105.0)),
-                        tint: spright::Color::new(0xff, 0xff, 0x00, 0x88),
+                        tint: spright::Color::new(0xff, 0xff, 0x00, 0x88).into(),
+                        ..Default::default()
                     }],
-                },
+                ),
             ],
         );
     }
 
     pub fn render<'rpass>(&'rpass self, rpass: &mut RenderPass<'rpass>) {
-        self.spright_renderer.render(rpass);
+        self.spright_renderer.render(rpass, &self.batch);
     }
 }
 
@@ -7,6 +7,15 @@ pub struct Sprite<'a> {
     /// Texture to draw with.
     pub texture: &'a wgpu::Texture,
 
+    /// Palette texture if `texture` holds palette indices. See [`crate::Group::palette`].
+    pub palette: Option<&'a wgpu::Texture>,
+
+    /// Row of `palette` to look colors up in.
+    pub palette_row: u32,
+
+    /// Normal map to light the sprite with. See [`crate::Group::normal_map`].
+    pub normal_map: Option<&'a wgpu::Texture>,
+
     /// Source offset from the texture.
     pub src_offset: IVec2,
 
@@ -20,19 +29,48 @@ pub struct Sprite<'a> {
     pub transform: Affine2,
 
     /// Tint.
-    pub tint: crate::Color,
+    pub tint: crate::LinearColor,
+
+    /// Color added after tinting and [`Sprite::color_matrix`].
+    pub color_offset: crate::LinearColor,
+
+    /// Color matrix applied after tinting, or `None` to leave colors unchanged.
+    pub color_matrix: Option<crate::color_matrix::ColorMatrix>,
+
+    /// Outline, or `None` for no outline.
+    pub outline: Option<crate::Outline>,
+
+    /// Drop shadow, or `None` for no shadow.
+    pub drop_shadow: Option<crate::DropShadow>,
+
+    /// Material to draw with, or `None` to use the default shader.
+    pub material: Option<&'a crate::material::Material>,
 }
 
-/// Batches a flat list of [`Sprite`]s into groups with textures.
+/// Batches a flat list of [`Sprite`]s into groups with textures, palettes, normal maps and
+/// materials.
 pub fn batch<'a>(sprites: &'a [Sprite]) -> Vec<crate::Group<'a>> {
     sprites
         .iter()
-        .chunk_by(|s| s.texture)
+        .chunk_by(|s| {
+            (
+                s.texture,
+                s.palette,
+                s.normal_map,
+                s.material.map(|m| m as *const _),
+            )
+        })
         .into_iter()
         .map(|(_, chunk)| {
             let chunk = chunk.collect::<Vec<_>>();
             crate::Group {
                 texture: chunk.first().unwrap().texture,
+                palette: chunk.first().unwrap().palette,
+                normal_map: chunk.first().unwrap().normal_map,
+                material: chunk.first().unwrap().material,
+                lod_bias: 0.0,
+                alpha_mode: None,
+                texel_density: None,
                 items: chunk
                     .into_iter()
                     .map(|s| crate::Item {
@@ -41,6 +79,11 @@ pub fn batch<'a>(sprites: &'a [Sprite]) -> Vec<crate::Group<'a>> {
                         src_layer: s.src_layer,
                         transform: s.transform,
                         tint: s.tint,
+                        color_offset: s.color_offset,
+                        color_matrix: s.color_matrix,
+                        palette_row: s.palette_row,
+                        outline: s.outline,
+                        drop_shadow: s.drop_shadow,
                     })
                     .collect::<Vec<_>>(),
             }
@@ -0,0 +1,75 @@
+//! Bind groups reused across prepares.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Number of prepares an entry may go unused for before it is evicted.
+///
+/// Entries hold on to their textures, so this also bounds how long a dropped texture stays
+/// alive.
+const EVICT_AFTER: u64 = 60;
+
+/// Identifies what a bind group binds.
+///
+/// Keys hold handles to their textures, so a texture can't be dropped and another created in
+/// its place while its entries are cached.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum BindGroupKey {
+    Texture {
+        texture: wgpu::Texture,
+        palette: Option<wgpu::Texture>,
+        sampler: wgpu::Sampler,
+        uniforms_offset: wgpu::BufferAddress,
+    },
+    Lighting {
+        normal_map: wgpu::Texture,
+    },
+    Bindless {
+        textures: Vec<wgpu::Texture>,
+        sampler: wgpu::Sampler,
+        uniforms_offset: wgpu::BufferAddress,
+    },
+}
+
+struct Entry {
+    bind_group: Arc<wgpu::BindGroup>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+pub(crate) struct BindGroupCache {
+    entries: HashMap<BindGroupKey, Entry>,
+    generation: u64,
+}
+
+impl BindGroupCache {
+    /// Starts a prepare.
+    pub fn begin(&mut self) {
+        self.generation += 1;
+    }
+
+    /// Drops every entry, e.g. because a buffer they bind was reallocated.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn get_or_insert_with(
+        &mut self,
+        key: BindGroupKey,
+        create: impl FnOnce() -> wgpu::BindGroup,
+    ) -> Arc<wgpu::BindGroup> {
+        let entry = self.entries.entry(key).or_insert_with(|| Entry {
+            bind_group: Arc::new(create()),
+            last_used: 0,
+        });
+        entry.last_used = self.generation;
+        entry.bind_group.clone()
+    }
+
+    /// Evicts entries that haven't been used for [`EVICT_AFTER`] prepares.
+    pub fn evict(&mut self) {
+        let generation = self.generation;
+        self.entries
+            .retain(|_, entry| generation - entry.last_used < EVICT_AFTER);
+    }
+}
@@ -0,0 +1,336 @@
+//! Drawing runs of groups in one draw call through a texture binding array.
+//!
+//! Each vertex carries an index into the array, so consecutive groups with different textures
+//! share a bind group and a draw. Devices without binding arrays draw every group on its own.
+
+use crate::{AlphaMode, Group, RendererOptions, SpritePipelines, TextureUniforms};
+use encase::ShaderSize as _;
+
+/// Device features the bindless path needs.
+pub(crate) const FEATURES: wgpu::Features = wgpu::Features::TEXTURE_BINDING_ARRAY
+    .union(wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+/// Largest binding array created, even if the device allows more.
+const MAX_TEXTURES: u32 = 256;
+
+pub(crate) struct Bindless {
+    pub max_textures: u32,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub shader: wgpu::ShaderModule,
+    pub render_pipelines: SpritePipelines,
+}
+
+impl Bindless {
+    /// Returns `None` if the device lacks [`FEATURES`] or can't bind enough textures.
+    pub fn new(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        options: &RendererOptions,
+        target_uniforms_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Option<Self> {
+        if !device.features().contains(FEATURES) {
+            return None;
+        }
+
+        // The effects texture takes one of the stage's sampled textures.
+        let max_textures = device
+            .limits()
+            .max_sampled_textures_per_shader_stage
+            .saturating_sub(1)
+            .min(MAX_TEXTURES)
+            .min(
+                (device.limits().max_uniform_buffer_binding_size as u64
+                    / TextureUniforms::SHADER_SIZE.get()) as u32,
+            );
+        if max_textures < 2 {
+            return None;
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: bindless_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: std::num::NonZeroU32::new(max_textures),
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("spright: bindless_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("sprite_bindings_bindless.wgsl")
+                    .replace("MAX_TEXTURES", &max_textures.to_string())
+                    + include_str!("shader.wgsl"))
+                .into(),
+            ),
+        });
+
+        Some(Self {
+            render_pipelines: SpritePipelines::new(
+                device,
+                "spright: bindless_render_pipeline",
+                texture_format,
+                options,
+                &[&bind_group_layout, target_uniforms_bind_group_layout],
+                &shader,
+                "fs_main",
+            ),
+            max_textures,
+            bind_group_layout,
+            shader,
+        })
+    }
+
+    /// Size, in bytes, of a run's texture uniforms.
+    pub fn uniforms_size(&self) -> wgpu::BufferAddress {
+        TextureUniforms::SHADER_SIZE.get() * self.max_textures as wgpu::BufferAddress
+    }
+}
+
+/// Consecutive groups drawn together.
+pub(crate) struct Run<'a> {
+    pub groups: std::ops::Range<usize>,
+    pub alpha_mode: AlphaMode,
+    /// Textures and LOD biases, in texture index order.
+    pub slots: Vec<(&'a wgpu::Texture, f32)>,
+}
+
+/// Returns whether a group can be drawn from the binding array.
+///
+/// Palettes, integer textures, normal maps and materials need bindings of their own.
+fn is_eligible(group: &Group<'_>) -> bool {
+    group.palette.is_none()
+        && group.normal_map.is_none()
+        && group.material.is_none()
+        && group.texture.format() != wgpu::TextureFormat::R8Uint
+}
+
+/// Splits groups into runs of consecutive eligible groups with the same alpha mode and at most
+/// `max_textures` distinct textures and LOD biases.
+///
+/// Returns the runs of more than one group and each group's texture index, which only the
+/// bindless shader reads.
+pub(crate) fn plan_runs<'a>(
+    groups: &[Group<'a>],
+    max_textures: usize,
+    alpha_mode: impl Fn(&Group<'_>) -> AlphaMode,
+) -> (Vec<Run<'a>>, Vec<u32>) {
+    let mut runs: Vec<Run<'a>> = vec![];
+    let mut texture_indices = vec![0; groups.len()];
+    for (i, group) in groups.iter().enumerate() {
+        if !is_eligible(group) {
+            continue;
+        }
+        let alpha_mode = alpha_mode(group);
+        let slot = (group.texture, group.lod_bias);
+        let find = |run: &Run<'_>| {
+            run.slots.iter().position(|&(texture, lod_bias)| {
+                // Textures compare by identity, like the bind group cache's keys.
+                texture == slot.0 && lod_bias.to_bits() == slot.1.to_bits()
+            })
+        };
+
+        let run = match runs.last_mut() {
+            Some(run)
+                if run.groups.end == i
+                    && run.alpha_mode == alpha_mode
+                    && (run.slots.len() < max_textures || find(run).is_some()) =>
+            {
+                run
+            }
+            _ => {
+                runs.push(Run {
+                    groups: i..i,
+                    alpha_mode,
+                    slots: vec![],
+                });
+                runs.last_mut().unwrap()
+            }
+        };
+        let index = find(run).unwrap_or_else(|| {
+            run.slots.push(slot);
+            run.slots.len() - 1
+        });
+        texture_indices[i] = index as u32;
+        run.groups.end = i + 1;
+    }
+
+    // A single group gains nothing from the binding array.
+    runs.retain(|run| run.groups.len() > 1);
+    (runs, texture_indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    fn group(texture: &wgpu::Texture) -> Group<'_> {
+        Group::new(texture, vec![])
+    }
+
+    fn plan<'a>(groups: &[Group<'a>], max_textures: usize) -> (Vec<Run<'a>>, Vec<u32>) {
+        plan_runs(groups, max_textures, |group| {
+            group.alpha_mode.unwrap_or(AlphaMode::Straight)
+        })
+    }
+
+    #[test]
+    fn interleaved_textures_share_a_run() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let [a, b] = [(); 2].map(|_| texture(&device, wgpu::TextureFormat::Rgba8Unorm));
+
+        let (runs, texture_indices) = plan(&[group(&a), group(&b), group(&a), group(&b)], 8);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].groups, 0..4);
+        assert_eq!(runs[0].slots.len(), 2);
+        assert_eq!(texture_indices, [0, 1, 0, 1]);
+
+        // Handles to the same texture share a slot.
+        let a_clone = a.clone();
+        let (runs, texture_indices) = plan(&[group(&a), group(&b), group(&a_clone)], 8);
+        assert_eq!(runs[0].slots.len(), 2);
+        assert_eq!(texture_indices, [0, 1, 0]);
+
+        // The same texture with a different LOD bias needs its own uniforms, so its own slot.
+        let (runs, texture_indices) = plan(
+            &[
+                group(&a),
+                Group {
+                    lod_bias: -1.0,
+                    ..group(&a)
+                },
+            ],
+            8,
+        );
+        assert_eq!(runs[0].slots.len(), 2);
+        assert_eq!(texture_indices, [0, 1]);
+    }
+
+    #[test]
+    fn ineligible_groups_split_runs() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let [a, b, c] = [(); 3].map(|_| texture(&device, wgpu::TextureFormat::Rgba8Unorm));
+        let indices = texture(&device, wgpu::TextureFormat::R8Uint);
+        let palette = texture(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let material = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm)
+            .create_material(
+                &device,
+                &crate::material::MaterialDescriptor {
+                    label: None,
+                    source: "fn material(in: MaterialInput) -> vec4<f32> { return in.sample; }",
+                    uniforms: None,
+                    textures: &[],
+                },
+            )
+            .unwrap();
+
+        for ineligible in [
+            Group {
+                palette: Some(&palette),
+                ..group(&c)
+            },
+            Group {
+                normal_map: Some(&palette),
+                ..group(&c)
+            },
+            Group {
+                material: Some(&material),
+                ..group(&c)
+            },
+            group(&indices),
+        ] {
+            let (runs, _) = plan(&[group(&a), group(&b), ineligible, group(&b), group(&a)], 8);
+            assert_eq!(
+                runs.iter()
+                    .map(|run| run.groups.clone())
+                    .collect::<Vec<_>>(),
+                [0..2, 3..5]
+            );
+        }
+    }
+
+    #[test]
+    fn runs_split_on_alpha_mode_and_max_textures() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let textures = [(); 5].map(|_| texture(&device, wgpu::TextureFormat::Rgba8Unorm));
+
+        let (runs, texture_indices) = plan(&textures.each_ref().map(group), 2);
+        assert_eq!(
+            runs.iter()
+                .map(|run| run.groups.clone())
+                .collect::<Vec<_>>(),
+            [0..2, 2..4]
+        );
+        assert_eq!(texture_indices[..4], [0, 1, 0, 1]);
+
+        let (runs, _) = plan(
+            &[
+                group(&textures[0]),
+                group(&textures[1]),
+                Group {
+                    alpha_mode: Some(AlphaMode::Premultiplied),
+                    ..group(&textures[2])
+                },
+                Group {
+                    alpha_mode: Some(AlphaMode::Premultiplied),
+                    ..group(&textures[3])
+                },
+                group(&textures[4]),
+            ],
+            8,
+        );
+        assert_eq!(
+            runs.iter()
+                .map(|run| run.groups.clone())
+                .collect::<Vec<_>>(),
+            [0..2, 2..4]
+        );
+        assert_eq!(runs[1].alpha_mode, AlphaMode::Premultiplied);
+    }
+}
@@ -0,0 +1,179 @@
+//! Color matrices for recoloring sprites.
+
+/// An affine transform of RGBA colors, applied after the tint.
+///
+/// Each row computes one output channel from the input channels and a constant:
+///
+/// ```text
+/// r' = m[0][0] * r + m[0][1] * g + m[0][2] * b + m[0][3] * a + m[0][4]
+/// ```
+///
+/// Colors are in [`crate::RendererOptions::tint_color_space`] with straight alpha, and the helpers
+/// use the same coefficients as CSS filter effects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMatrix(pub [[f32; 5]; 4]);
+
+impl Default for ColorMatrix {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ColorMatrix {
+    /// Leaves colors unchanged.
+    pub const IDENTITY: Self = Self([
+        [1.0, 0.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    /// Creates a matrix from the 3x3 RGB part, leaving alpha unchanged.
+    fn from_rgb(m: [[f32; 3]; 3]) -> Self {
+        Self([
+            [m[0][0], m[0][1], m[0][2], 0.0, 0.0],
+            [m[1][0], m[1][1], m[1][2], 0.0, 0.0],
+            [m[2][0], m[2][1], m[2][2], 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Scales saturation, where 0 is grayscale, 1 is unchanged and values above 1 oversaturate.
+    pub fn saturation(s: f32) -> Self {
+        Self::from_rgb([
+            [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s],
+            [0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s],
+            [0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s],
+        ])
+    }
+
+    /// Converts to grayscale, where `amount` is from 0 (unchanged) to 1 (fully gray).
+    pub fn grayscale(amount: f32) -> Self {
+        Self::saturation(1.0 - amount)
+    }
+
+    /// Converts to sepia, where `amount` is from 0 (unchanged) to 1 (fully sepia).
+    pub fn sepia(amount: f32) -> Self {
+        let a = 1.0 - amount;
+        Self::from_rgb([
+            [0.393 + 0.607 * a, 0.769 - 0.769 * a, 0.189 - 0.189 * a],
+            [0.349 - 0.349 * a, 0.686 + 0.314 * a, 0.168 - 0.168 * a],
+            [0.272 - 0.272 * a, 0.534 - 0.534 * a, 0.131 + 0.869 * a],
+        ])
+    }
+
+    /// Rotates hues by `angle` radians, preserving luminance.
+    pub fn hue_rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::from_rgb([
+            [
+                0.213 + cos * 0.787 - sin * 0.213,
+                0.715 - cos * 0.715 - sin * 0.715,
+                0.072 - cos * 0.072 + sin * 0.928,
+            ],
+            [
+                0.213 - cos * 0.213 + sin * 0.143,
+                0.715 + cos * 0.285 + sin * 0.140,
+                0.072 - cos * 0.072 - sin * 0.283,
+            ],
+            [
+                0.213 - cos * 0.213 - sin * 0.787,
+                0.715 - cos * 0.715 + sin * 0.715,
+                0.072 + cos * 0.928 + sin * 0.072,
+            ],
+        ])
+    }
+
+    /// Adds `brightness` to every color channel and multiplies contrast around middle gray by
+    /// `contrast`.
+    pub fn brightness_contrast(brightness: f32, contrast: f32) -> Self {
+        let offset = 0.5 - 0.5 * contrast + brightness;
+        Self([
+            [contrast, 0.0, 0.0, 0.0, offset],
+            [0.0, contrast, 0.0, 0.0, offset],
+            [0.0, 0.0, contrast, 0.0, offset],
+            [0.0, 0.0, 0.0, 1.0, 0.0],
+        ])
+    }
+
+    /// Returns a matrix that applies `self` and then `next`.
+    pub fn then(&self, next: &ColorMatrix) -> Self {
+        let (a, b) = (&self.0, &next.0);
+        Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| {
+                let sum = (0..4).map(|k| b[i][k] * a[k][j]).sum::<f32>();
+                if j == 4 {
+                    sum + b[i][4]
+                } else {
+                    sum
+                }
+            })
+        }))
+    }
+
+    /// Returns the matrix as five columns, the last being the constant offsets.
+    pub(crate) fn columns(&self) -> [[f32; 4]; 5] {
+        std::array::from_fn(|j| std::array::from_fn(|i| self.0[i][j]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(m: &ColorMatrix, c: [f32; 4]) -> [f32; 4] {
+        std::array::from_fn(|i| (0..4).map(|k| m.0[i][k] * c[k]).sum::<f32>() + m.0[i][4])
+    }
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        assert!(
+            actual
+                .iter()
+                .zip(expected)
+                .all(|(a, e)| (a - e).abs() < 1e-3),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    const COLOR: [f32; 4] = [0.8, 0.3, 0.1, 0.6];
+
+    #[test]
+    fn neutral_presets_are_identity() {
+        assert_eq!(ColorMatrix::default(), ColorMatrix::IDENTITY);
+        for m in [
+            ColorMatrix::saturation(1.0),
+            ColorMatrix::grayscale(0.0),
+            ColorMatrix::sepia(0.0),
+            ColorMatrix::hue_rotate(0.0),
+            ColorMatrix::hue_rotate(std::f32::consts::TAU),
+            ColorMatrix::brightness_contrast(0.0, 1.0),
+        ] {
+            assert_close(apply(&m, COLOR), COLOR);
+        }
+    }
+
+    #[test]
+    fn grayscale_keeps_luminance_and_alpha() {
+        let gray = 0.213 * COLOR[0] + 0.715 * COLOR[1] + 0.072 * COLOR[2];
+        assert_close(
+            apply(&ColorMatrix::grayscale(1.0), COLOR),
+            [gray, gray, gray, COLOR[3]],
+        );
+    }
+
+    #[test]
+    fn brightness_contrast_pivots_on_middle_gray() {
+        let m = ColorMatrix::brightness_contrast(0.1, 2.0);
+        assert_close(apply(&m, [0.5, 0.25, 1.0, 0.5]), [0.6, 0.1, 1.6, 0.5]);
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let a = ColorMatrix::sepia(0.7);
+        let b = ColorMatrix::brightness_contrast(0.2, 1.5);
+        assert_close(apply(&a.then(&b), COLOR), apply(&b, apply(&a, COLOR)));
+        assert_close(apply(&b.then(&a), COLOR), apply(&a, apply(&b, COLOR)));
+        assert_eq!(a.then(&ColorMatrix::IDENTITY), a);
+        assert_eq!(ColorMatrix::IDENTITY.then(&a), a);
+    }
+}
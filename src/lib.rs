@@ -1,18 +1,112 @@
-use encase::{DynamicUniformBuffer, ShaderSize, ShaderType, UniformBuffer};
+use bytemuck::Zeroable as _;
+use encase::{DynamicUniformBuffer, ShaderSize, UniformBuffer};
 use glam::*;
+use wgpu::util::DeviceExt as _;
 
 pub mod batch;
+mod bind_group_cache;
+mod bindless;
+pub mod color_matrix;
+pub mod lighting;
+pub mod material;
+pub mod mipmap;
+pub mod multisample;
+pub mod particles;
+pub mod picking;
+pub mod pixel_perfect;
+pub mod postprocess;
+pub mod premultiply;
+pub mod render_target;
+pub mod shadows;
+mod uniforms;
+pub mod virtual_canvas;
+
+use material::{Material, MaterialPipeline, MaterialPipelineKey};
+use uniforms::*;
 
 pub type Color = rgb::RGBA8;
 
+/// A color with linear float channels and straight alpha.
+///
+/// Unlike [`Color`], channels are not sRGB-encoded and may go above 1.0, e.g. to push emissive
+/// sprites into HDR on float targets.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LinearColor {
+    /// Red.
+    pub r: f32,
+
+    /// Green.
+    pub g: f32,
+
+    /// Blue.
+    pub b: f32,
+
+    /// Alpha.
+    pub a: f32,
+}
+
+impl LinearColor {
+    /// Opaque white.
+    pub const WHITE: Self = Self::new(1.0, 1.0, 1.0, 1.0);
+
+    /// Creates a new color.
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl From<Color> for LinearColor {
+    /// Decodes an sRGB-encoded color.
+    fn from(color: Color) -> Self {
+        let [r, g, b, a] = color_to_vec4(color);
+        Self::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+}
+
 /// Represents a group of sprites to draw from the same texture.
 #[derive(Debug, Clone)]
 pub struct Group<'a> {
     /// Texture to draw with.
+    ///
+    /// If [`Group::palette`] is set, this must be an `R8Uint` or `R8Unorm` texture of palette
+    /// indices.
     pub texture: &'a wgpu::Texture,
 
+    /// Palette texture for indexed textures, or `None` if the texture holds colors.
+    ///
+    /// Each row is a palette, and each column is the color for that index. Items pick a row with
+    /// [`Item::palette_row`], so recoloring needs no extra copies of the index texture. Only the
+    /// first layer of the palette is used.
+    pub palette: Option<&'a wgpu::Texture>,
+
     /// Items in the group.
     pub items: Vec<Item>,
+
+    /// Normal map with the same size and layout as the texture, or `None` to draw unlit.
+    ///
+    /// Normal maps are in a non-sRGB format with green pointing up. Lit groups are lit with the
+    /// lights set by [`Renderer::prepare_lighting`]. Groups with a material are drawn unlit.
+    pub normal_map: Option<&'a wgpu::Texture>,
+
+    /// Material to draw with, or `None` to use the default shader.
+    pub material: Option<&'a Material>,
+
+    /// Bias added to the mip level selected when sampling the texture.
+    ///
+    /// Negative values select sharper mip levels. This has no effect on textures without mip
+    /// levels.
+    pub lod_bias: f32,
+
+    /// How the texture's alpha is encoded, or `None` to use [`RendererOptions::alpha_mode`].
+    pub alpha_mode: Option<AlphaMode>,
+
+    /// Texels per logical pixel, or `None` to pick one from the texture.
+    ///
+    /// Item transforms map `src_size / texel_density` logical pixels to the target, so textures
+    /// rasterized at [`Renderer::scale_factor`] stay sharp without changing their transforms.
+    /// `None` picks the scale factor for masks, which are usually rasterized per frame at the
+    /// window's density, and 1 otherwise.
+    pub texel_density: Option<f32>,
 }
 
 /// Represents a sprite to draw.
@@ -27,59 +121,613 @@ pub struct Item {
     /// Source layer.
     pub src_layer: u32,
 
-    /// Target transform.
+    /// Target transform, in logical pixels. See [`Renderer::set_scale_factor`].
     pub transform: Affine2,
 
     /// Tint.
-    pub tint: Color,
+    ///
+    /// [`Color`]s can be converted with `.into()`. The tint is converted to
+    /// [`RendererOptions::tint_color_space`] before being applied, so it looks the same regardless
+    /// of the target format.
+    pub tint: LinearColor,
+
+    /// Color added after tinting and [`Item::color_matrix`], e.g. white with zero alpha to flash
+    /// the sprite solid white.
+    pub color_offset: LinearColor,
+
+    /// Color matrix applied after tinting, or `None` to leave colors unchanged.
+    pub color_matrix: Option<color_matrix::ColorMatrix>,
+
+    /// Row of [`Group::palette`] to look colors up in. Ignored if the group has no palette.
+    pub palette_row: u32,
+
+    /// Outline drawn around the opaque texels of the source rect, or `None` for no outline.
+    pub outline: Option<Outline>,
+
+    /// Shadow drawn under the item and its outline, or `None` for no shadow.
+    pub drop_shadow: Option<DropShadow>,
+}
+
+impl<'a> Group<'a> {
+    /// Creates a group that draws `items` from `texture` unlit with the default shader, without a
+    /// palette and with the renderer's alpha mode.
+    pub fn new(texture: &'a wgpu::Texture, items: Vec<Item>) -> Self {
+        Self {
+            texture,
+            palette: None,
+            items,
+            normal_map: None,
+            material: None,
+            lod_bias: 0.0,
+            alpha_mode: None,
+            texel_density: None,
+        }
+    }
+}
+
+impl Default for Item {
+    /// An untinted item at the origin with an empty source rect and no effects.
+    fn default() -> Self {
+        Self {
+            src_offset: IVec2::ZERO,
+            src_size: UVec2::ZERO,
+            src_layer: 0,
+            transform: Affine2::IDENTITY,
+            tint: LinearColor::WHITE,
+            color_offset: LinearColor::default(),
+            color_matrix: None,
+            palette_row: 0,
+            outline: None,
+            drop_shadow: None,
+        }
+    }
+}
+
+/// An outline around the opaque texels of an [`Item`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outline {
+    /// Color.
+    pub color: LinearColor,
+
+    /// Thickness, in logical pixels.
+    pub thickness: f32,
+}
+
+/// A blurred copy of an [`Item`]'s silhouette drawn under it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropShadow {
+    /// Offset from the item, in logical pixels.
+    pub offset: Vec2,
+
+    /// Blur radius, in logical pixels. 0 gives a hard shadow.
+    pub blur: f32,
+
+    /// Color.
+    pub color: LinearColor,
+}
+
+/// Error returned when a user-supplied shader cannot be used.
+#[derive(Debug, Clone)]
+pub enum ShaderError {
+    /// The shader failed to parse or validate.
+    Compile(String),
+
+    /// The shader declares a binding that is not provided.
+    InvalidBinding { group: u32, binding: u32 },
+
+    /// The shader declares a provided binding with a different type.
+    MismatchedBinding { group: u32, binding: u32 },
+
+    /// A texture provided to the shader has more than one layer, so can't be bound as a
+    /// `texture_2d<f32>`.
+    LayeredTexture { index: usize },
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ShaderError::Compile(message) => write!(f, "shader error: {message}"),
+            ShaderError::InvalidBinding { group, binding } => write!(
+                f,
+                "shader declares @group({group}) @binding({binding}), which is not provided"
+            ),
+            ShaderError::MismatchedBinding { group, binding } => write!(
+                f,
+                "shader declares @group({group}) @binding({binding}) with a different type than \
+                 the one provided"
+            ),
+            ShaderError::LayeredTexture { index } => write!(
+                f,
+                "texture {index} has more than one layer, so can't be bound as a texture_2d"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+/// A resource provided to a user-supplied shader.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProvidedBinding {
+    /// A `var<uniform>` block.
+    Uniform,
+
+    /// A `texture_2d<f32>`.
+    Texture2d,
+}
+
+impl ProvidedBinding {
+    fn matches(self, module: &naga::Module, var: &naga::GlobalVariable) -> bool {
+        match self {
+            ProvidedBinding::Uniform => var.space == naga::AddressSpace::Uniform,
+            ProvidedBinding::Texture2d => matches!(
+                module.types[var.ty].inner,
+                naga::TypeInner::Image {
+                    dim: naga::ImageDimension::D2,
+                    arrayed: false,
+                    class: naga::ImageClass::Sampled {
+                        kind: naga::ScalarKind::Float,
+                        multi: false,
+                    },
+                }
+            ),
+        }
+    }
+}
+
+/// Parses and validates a WGSL module made of the library's `prelude` followed by user source,
+/// checking that every binding the user source declares is `provided` with a matching type.
+fn validate_shader(
+    prelude: &str,
+    user_source: &str,
+    provided: impl Fn(u32, u32) -> Option<ProvidedBinding>,
+) -> Result<String, ShaderError> {
+    let source = [prelude, user_source].join("\n");
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|e| ShaderError::Compile(e.emit_to_string(&source)))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| ShaderError::Compile(e.emit_to_string(&source)))?;
+
+    for (handle, var) in module.global_variables.iter() {
+        let Some(binding) = &var.binding else {
+            continue;
+        };
+        // The prelude's own bindings are laid out by the library.
+        let declared_by_user = module
+            .global_variables
+            .get_span(handle)
+            .to_range()
+            .is_some_and(|range| range.start >= prelude.len());
+        if !declared_by_user {
+            continue;
+        }
+        match provided(binding.group, binding.binding) {
+            None => {
+                return Err(ShaderError::InvalidBinding {
+                    group: binding.group,
+                    binding: binding.binding,
+                })
+            }
+            Some(expected) if !expected.matches(&module, var) => {
+                return Err(ShaderError::MismatchedBinding {
+                    group: binding.group,
+                    binding: binding.binding,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(source)
+}
+
+/// Options for creating a [`Renderer`].
+#[derive(Debug, Clone)]
+pub struct RendererOptions {
+    /// Number of samples per pixel of the color attachment the renderer draws to.
+    ///
+    /// Values above 1 enable multisample anti-aliasing. See [`multisample::MultisampleTarget`] for a
+    /// helper that owns the multisampled attachment.
+    pub sample_count: u32,
+
+    /// Depth/stencil state to draw with, for render passes that have a depth/stencil attachment.
+    ///
+    /// The attachment must have the same sample count as the color attachment.
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+
+    /// How textures are filtered when sampled.
+    pub filtering: Filtering,
+
+    /// How texture alpha is encoded and blended, unless overridden by [`Group::alpha_mode`].
+    pub alpha_mode: AlphaMode,
+
+    /// Color space tints are converted to before being multiplied with texture samples, or
+    /// `None` to pick one from the target format.
+    ///
+    /// Samples of sRGB textures and colors written to sRGB targets are linear, so `None` picks
+    /// [`ColorSpace::Linear`] for sRGB and float targets and [`ColorSpace::Srgb`] otherwise.
+    ///
+    /// Only the target format is considered. Tinted colors are written to the target as they
+    /// are, so the target's encoding decides how a tint shows up. An sRGB texture drawn to a
+    /// non-sRGB target gets sRGB tints: its white still tints to the tint, even though its other
+    /// colors are written linear.
+    pub tint_color_space: Option<ColorSpace>,
+
+    /// How the vertex, index and uniform buffers of batches, post-processing and light maps grow
+    /// and shrink.
+    pub buffer_capacity: BufferCapacity,
+
+    /// How transformed quad corners are aligned to the target's pixel grid.
+    ///
+    /// Snapping keeps pixel-art sprites with fractional translations from shimmering as they move.
+    pub pixel_snap: pixel_perfect::PixelSnap,
+
+    /// Whether to draw consecutive groups with different textures in one draw call through a
+    /// texture binding array, when the device has [`wgpu::Features::TEXTURE_BINDING_ARRAY`] and
+    /// [`wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING`].
+    ///
+    /// Groups with palettes, integer textures, normal maps or materials are always drawn on their
+    /// own, as is every group on devices without the features.
+    pub bindless: bool,
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            sample_count: 1,
+            depth_stencil: None,
+            filtering: Filtering::Nearest,
+            alpha_mode: AlphaMode::Straight,
+            tint_color_space: None,
+            pixel_snap: pixel_perfect::PixelSnap::Off,
+            buffer_capacity: BufferCapacity::default(),
+            bindless: true,
+        }
+    }
+}
+
+/// How the buffers of [`PreparedBatch`]es, [`postprocess::PostProcess`] and
+/// [`shadows::LightMap`] grow and shrink as the amount of data changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferCapacity {
+    /// Factor a buffer's size is multiplied by when data doesn't fit, so slowly growing sprite
+    /// counts reallocate rarely. Values below 1 are treated as 1.
+    pub growth_factor: f32,
+
+    /// Smallest size, in bytes, a buffer is reallocated to.
+    pub min_size: u64,
+
+    /// Number of consecutive prepares using at most [`BufferCapacity::shrink_threshold`] of a
+    /// buffer after which it is shrunk, or `None` to never shrink.
+    pub shrink_after: Option<u32>,
+
+    /// Fraction of a buffer's size at or below which a prepare counts towards shrinking it.
+    pub shrink_threshold: f32,
+
+    /// Size, in bytes, of the chunks of the staging belt data is uploaded through.
+    ///
+    /// Uploads larger than this get a dedicated chunk.
+    pub staging_chunk_size: u64,
+}
+
+impl Default for BufferCapacity {
+    fn default() -> Self {
+        Self {
+            growth_factor: 2.0,
+            min_size: 4096,
+            shrink_after: Some(300),
+            shrink_threshold: 0.25,
+            staging_chunk_size: 1 << 20,
+        }
+    }
+}
+
+/// Which items [`Renderer::prepare`] skips.
+///
+/// Items are tested by the bounds of their transformed quads, including outlines and drop
+/// shadows.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Culling {
+    /// Every item is prepared.
+    #[default]
+    Off,
+
+    /// Items entirely outside the target are skipped.
+    Target,
+
+    /// Items entirely outside a rect are skipped.
+    View {
+        /// Top-left corner, in logical pixels.
+        min: Vec2,
+
+        /// Bottom-right corner, in logical pixels.
+        max: Vec2,
+    },
+}
+
+/// How textures are filtered when sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filtering {
+    /// Nearest-neighbor filtering, including between mip levels.
+    Nearest,
+
+    /// Nearest-neighbor magnification, with trilinear filtering when minifying.
+    ///
+    /// This keeps upscaled pixel art crisp while avoiding shimmering when downscaling. Textures
+    /// need mip levels, e.g. from [`mipmap::MipmapGenerator`], for this to have an effect.
+    Mipmapped,
+
+    /// Bilinear magnification, with trilinear filtering when minifying.
+    Trilinear,
+}
+
+impl Filtering {
+    fn sampler_descriptor(self) -> wgpu::SamplerDescriptor<'static> {
+        let (mag_filter, min_filter, mipmap_filter) = match self {
+            Filtering::Nearest => (
+                wgpu::FilterMode::Nearest,
+                wgpu::FilterMode::Nearest,
+                wgpu::FilterMode::Nearest,
+            ),
+            Filtering::Mipmapped => (
+                wgpu::FilterMode::Nearest,
+                wgpu::FilterMode::Linear,
+                wgpu::FilterMode::Linear,
+            ),
+            Filtering::Trilinear => (
+                wgpu::FilterMode::Linear,
+                wgpu::FilterMode::Linear,
+                wgpu::FilterMode::Linear,
+            ),
+        };
+        wgpu::SamplerDescriptor {
+            label: Some("spright: sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            ..Default::default()
+        }
+    }
+}
+
+/// How texture colors relate to their alpha.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// Colors are independent of alpha, and are blended with straight alpha blending.
+    Straight,
+
+    /// Colors are already multiplied by alpha, and are blended with premultiplied alpha blending.
+    ///
+    /// Tints stay straight, and are premultiplied before being applied. This avoids dark fringes
+    /// around filtered edges, and allows additive blending by drawing colors with zero alpha.
+    /// Textures can be premultiplied with the [`premultiply`] module.
+    Premultiplied,
+}
+
+/// Color space that colors are converted to for shading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// sRGB-encoded.
+    Srgb,
+
+    /// Linear.
+    Linear,
 }
 
 /// Encapsulates static state for rendering.
 pub struct Renderer {
-    render_pipeline: wgpu::RenderPipeline,
+    texture_format: wgpu::TextureFormat,
+    options: RendererOptions,
+    render_pipelines: SpritePipelines,
+    lit_render_pipelines: SpritePipelines,
+    bindless: Option<bindless::Bindless>,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    target_uniforms_buffer: wgpu::Buffer,
-    target_uniforms_bind_group: wgpu::BindGroup,
-    texture_uniforms_buffer: DynamicBuffer,
-    prepared_groups: Vec<PreparedGroup>,
-    vertex_buffer: DynamicBuffer,
-    index_buffer: DynamicBuffer,
+    target_uniforms_bind_group_layout: wgpu::BindGroupLayout,
+    lighting_bind_group_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
+    dummy_texture: wgpu::Texture,
+    dummy_index_texture: wgpu::Texture,
+    /// Dummies for array bindings, with two layers so that every backend binds them as arrays.
+    dummy_array_texture: wgpu::Texture,
+    dummy_index_array_texture: wgpu::Texture,
+    registered_textures: std::collections::HashMap<wgpu::Texture, RegisteredTexture>,
+    material_pipelines:
+        std::collections::HashMap<MaterialPipelineKey, std::sync::Arc<MaterialPipeline>>,
+    scale_factor: f32,
+    culling: Culling,
 }
 
+/// Per-item data, drawn as an instanced quad.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-    layer: u32,
+struct Instance {
+    /// Target positions of the quad's corners, in the order of a triangle strip.
+    corners: [[f32; 2]; 4],
+    /// Texture coordinates of the quad as min.xy, max.xy, including the effect margin.
+    tex_rect: [f32; 4],
+    /// Source rect as min.xy, max.xy.
+    src_rect: [f32; 4],
     tint: [f32; 4],
+    layer: u32,
+    palette_row: u32,
+    texture_index: u32,
+    /// Index into the batch's effects texture, or [`NO_EFFECTS`].
+    effects_index: u32,
 }
 
-#[repr(C)]
-#[derive(Copy, Clone, Debug, ShaderType)]
-struct TextureUniforms {
-    size: Vec3,
-    is_mask: u32,
+impl Instance {
+    const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Instance,
+        attributes: &wgpu::vertex_attr_array![
+            0 => Float32x4,
+            1 => Float32x4,
+            2 => Float32x4,
+            3 => Float32x4,
+            4 => Float32x4,
+            5 => Uint32,
+            6 => Uint32,
+            7 => Uint32,
+            8 => Uint32,
+        ],
+    };
 }
 
+/// [`Instance::effects_index`] of items without a color matrix, color offset, outline or drop
+/// shadow.
+const NO_EFFECTS: u32 = u32::MAX;
+
+/// Width of the effects texture, in texels. Matches `EFFECTS_TEXTURE_WIDTH` in `shader.wgsl`.
+const EFFECTS_TEXTURE_WIDTH: u32 = 2048;
+
+/// The color transform and effects of an item that has any, stored as texels of the batch's
+/// effects texture.
 #[repr(C)]
-#[derive(Copy, Clone, Debug, ShaderType)]
-struct TargetUniforms {
-    size: Vec3,
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Effects {
+    color_matrix: [[f32; 4]; 4],
+    /// The color matrix's translation column plus the color offset.
+    color_offset: [f32; 4],
+    outline_color: [f32; 4],
+    shadow_color: [f32; 4],
+    /// Outline thickness, shadow offset and shadow blur radius, in texels.
+    params: [f32; 4],
 }
 
-impl Vertex {
-    const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
-        array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
-        step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2=> Uint32, 3 => Float32x4],
-    };
+/// Texels of the effects texture per item.
+const EFFECTS_TEXELS: u32 = (std::mem::size_of::<Effects>() / 16) as u32;
+
+/// Returns how many items' effects fit in one effects texture on `device`.
+fn effects_per_page(device: &wgpu::Device) -> u32 {
+    (EFFECTS_TEXTURE_WIDTH / EFFECTS_TEXELS)
+        .saturating_mul(device.limits().max_texture_dimension_2d)
+}
+
+/// Splits draws where their instances' effects continue on the next effects page, and makes
+/// effects indices relative to their page.
+///
+/// Effects are numbered in instance order, so each page's instances are contiguous. Instances
+/// without effects stay on the page before them.
+fn split_effects_pages(
+    groups: Vec<PreparedGroup>,
+    instances: &mut [Instance],
+    effects_per_page: u32,
+) -> Vec<PreparedGroup> {
+    let mut split = Vec::with_capacity(groups.len());
+    let mut page = 0;
+    for group in groups {
+        let mut start = group.instance_start;
+        for i in group.instance_start..group.instance_end {
+            let instance = &mut instances[i as usize];
+            if instance.effects_index == NO_EFFECTS {
+                continue;
+            }
+            let instance_page = (instance.effects_index / effects_per_page) as usize;
+            instance.effects_index %= effects_per_page;
+            if instance_page != page {
+                if start < i {
+                    split.push(PreparedGroup {
+                        effects_page: page,
+                        instance_start: start,
+                        instance_end: i,
+                        ..group.clone()
+                    });
+                }
+                start = i;
+                page = instance_page;
+            }
+        }
+        split.push(PreparedGroup {
+            effects_page: page,
+            instance_start: start,
+            ..group
+        });
+    }
+    split
+}
+
+struct Geometry {
+    instances: Vec<Instance>,
+    effects: Vec<Effects>,
+    instance_ranges: Vec<std::ops::Range<u32>>,
+    item_ids: Vec<(usize, usize)>,
+    culled_count: usize,
+}
+
+/// Maps `f` over per-group work, in parallel if `parallel` is set.
+#[cfg(feature = "rayon")]
+fn map_groups<T: Send, U: Send>(
+    items: Vec<T>,
+    parallel: bool,
+    f: impl Fn(T) -> U + Send + Sync,
+) -> Vec<U> {
+    use rayon::prelude::*;
+    if parallel {
+        items.into_par_iter().map(f).collect()
+    } else {
+        items.into_iter().map(f).collect()
+    }
+}
+
+/// Maps `f` over per-group work. Without the `rayon` feature, this is always serial.
+#[cfg(not(feature = "rayon"))]
+fn map_groups<T, U>(items: Vec<T>, _parallel: bool, f: impl Fn(T) -> U) -> Vec<U> {
+    items.into_iter().map(f).collect()
+}
+
+/// Views of a texture registered with [`Renderer::register_texture`], created up front.
+struct RegisteredTexture {
+    array_view: wgpu::TextureView,
+    view: Option<wgpu::TextureView>,
 }
 
 struct DynamicBuffer {
     inner: wgpu::Buffer,
     label: Option<String>,
+    low_usage_writes: u32,
+}
+
+impl BufferCapacity {
+    /// Returns the size a buffer of `current` bytes should be reallocated to before `size` bytes
+    /// are written to it, or `None` to keep it. `low_usage_writes` counts consecutive writes
+    /// towards shrinking it.
+    fn resized(
+        &self,
+        current: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+        low_usage_writes: &mut u32,
+    ) -> Option<wgpu::BufferAddress> {
+        let grown = |size: wgpu::BufferAddress| {
+            ((size as f64 * self.growth_factor.max(1.0) as f64) as wgpu::BufferAddress)
+                .max(size)
+                .max(self.min_size)
+        };
+
+        if current < size {
+            *low_usage_writes = 0;
+            return Some(grown(current).max(size));
+        }
+
+        let shrink_after = self.shrink_after?;
+        if (size as f64) > current as f64 * self.shrink_threshold as f64 {
+            *low_usage_writes = 0;
+            return None;
+        }
+        *low_usage_writes += 1;
+        if *low_usage_writes < shrink_after {
+            return None;
+        }
+        *low_usage_writes = 0;
+        Some(grown(size)).filter(|&shrunk| shrunk < current)
+    }
 }
 
 impl DynamicBuffer {
@@ -87,32 +735,66 @@ impl DynamicBuffer {
         Self {
             inner: device.create_buffer(desc),
             label: desc.label.map(|v| v.to_string()),
+            low_usage_writes: 0,
         }
     }
 
-    fn reallocate(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) -> wgpu::Buffer {
-        let mut old = device.create_buffer(&wgpu::BufferDescriptor {
-            label: self.label.as_ref().map(|v| v.as_str()),
-            size,
+    fn reallocate(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) {
+        self.inner = device.create_buffer(&wgpu::BufferDescriptor {
+            label: self.label.as_deref(),
+            size: size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
             usage: self.inner.usage(),
-            mapped_at_creation: true,
+            mapped_at_creation: false,
         });
-        std::mem::swap(&mut old, &mut self.inner);
-        old
     }
 
-    fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) {
-        let size = data.len() as u64;
-        if self.inner.size() < size {
-            self.reallocate(device, size);
-            {
-                let mut view = self.inner.slice(..).get_mapped_range_mut();
-                view.copy_from_slice(data);
-            }
-            self.inner.unmap();
-        } else {
-            queue.write_buffer(&self.inner, 0, data);
+    /// Makes room for `size` bytes, growing or shrinking the buffer as `capacity` allows.
+    ///
+    /// Previous contents are not preserved when the buffer is reallocated.
+    fn reserve(
+        &mut self,
+        device: &wgpu::Device,
+        size: wgpu::BufferAddress,
+        capacity: &BufferCapacity,
+    ) -> bool {
+        let Some(new_size) = capacity.resized(self.inner.size(), size, &mut self.low_usage_writes)
+        else {
+            return false;
+        };
+        self.reallocate(device, new_size);
+        true
+    }
+
+    /// Writes `data` to the buffer, returning whether the buffer was reallocated.
+    fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+        capacity: &BufferCapacity,
+    ) -> bool {
+        let reallocated = self.reserve(device, data.len() as u64, capacity);
+        queue.write_buffer(&self.inner, 0, data);
+        reallocated
+    }
+
+    /// Records a copy of `data` into the buffer through `staging_belt`, returning whether the
+    /// buffer was reallocated.
+    fn write_staged(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        data: &[u8],
+        capacity: &BufferCapacity,
+    ) -> bool {
+        let reallocated = self.reserve(device, data.len() as u64, capacity);
+        if let Some(size) = wgpu::BufferSize::new(data.len() as u64) {
+            staging_belt
+                .write_buffer(encoder, &self.inner, 0, size, device)
+                .copy_from_slice(data);
         }
+        reallocated
     }
 }
 
@@ -124,16 +806,359 @@ impl std::ops::Deref for DynamicBuffer {
     }
 }
 
+fn multisample_state(options: &RendererOptions) -> wgpu::MultisampleState {
+    wgpu::MultisampleState {
+        count: options.sample_count,
+        ..Default::default()
+    }
+}
+
+fn color_target_state(
+    texture_format: wgpu::TextureFormat,
+    alpha_mode: AlphaMode,
+) -> wgpu::ColorTargetState {
+    wgpu::ColorTargetState {
+        format: texture_format,
+        blend: Some(match alpha_mode {
+            AlphaMode::Straight => wgpu::BlendState::ALPHA_BLENDING,
+            AlphaMode::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        }),
+        write_mask: wgpu::ColorWrites::all(),
+    }
+}
+
+/// Creates a 1x1 texture to bind in place of textures a group doesn't use.
+fn create_dummy_texture(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    layers: u32,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("spright: dummy_texture"),
+        size: wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: layers,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+/// Creates an effects texture with room for `rows` rows of [`Effects`].
+fn create_effects_texture(device: &wgpu::Device, rows: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("spright: effects_texture"),
+        size: wgpu::Extent3d {
+            width: EFFECTS_TEXTURE_WIDTH,
+            height: rows.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+/// Whether a texture is bound as an array rather than as a 2D texture. Some backends can't view
+/// single-layer textures as arrays, or multi-layer textures as 2D textures.
+fn is_layered(texture: &wgpu::Texture) -> bool {
+    texture.depth_or_array_layers() > 1
+}
+
+fn is_mask(texture: &wgpu::Texture, palette: Option<&wgpu::Texture>) -> bool {
+    palette.is_none() && texture.format() == wgpu::TextureFormat::R8Unorm
+}
+
+fn texture_uniforms(
+    texture: &wgpu::Texture,
+    palette: Option<&wgpu::Texture>,
+    lod_bias: f32,
+    alpha_mode: AlphaMode,
+) -> TextureUniforms {
+    TextureUniforms {
+        size: Vec3 {
+            x: texture.width() as f32,
+            y: texture.height() as f32,
+            z: 0.0,
+        },
+        is_mask: is_mask(texture, palette) as u32,
+        indexed: match (palette, texture.format()) {
+            (None, _) => 0,
+            (Some(_), wgpu::TextureFormat::R8Uint) => 2,
+            (Some(_), _) => 1,
+        },
+        lod_bias,
+        premultiplied: (alpha_mode == AlphaMode::Premultiplied) as u32,
+        layered: is_layered(texture) as u32 | (palette.is_some_and(is_layered) as u32) << 1,
+    }
+}
+
+fn target_uniforms(target_size: wgpu::Extent3d) -> TargetUniforms {
+    TargetUniforms {
+        size: Vec3 {
+            x: target_size.width as f32,
+            y: target_size.height as f32,
+            z: 0.0,
+        },
+    }
+}
+
+fn color_to_vec4(color: Color) -> [f32; 4] {
+    [
+        color.r as f32 / 255.0,
+        color.g as f32 / 255.0,
+        color.b as f32 / 255.0,
+        color.a as f32 / 255.0,
+    ]
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts a tint to `color_space`. Alpha is always linear.
+fn tint_to_vec4(color: LinearColor, color_space: ColorSpace) -> [f32; 4] {
+    let LinearColor { r, g, b, a } = color;
+    match color_space {
+        ColorSpace::Srgb => [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a],
+        ColorSpace::Linear => [r, g, b, a],
+    }
+}
+
+struct ItemEffects {
+    outline_color: [f32; 4],
+    shadow_color: [f32; 4],
+    params: [f32; 4],
+    margin: Vec2,
+}
+
+/// Converts an item's outline and drop shadow from logical pixels to source texels, given the
+/// linear part of the transform from texels to logical pixels.
+fn item_effects(item: &Item, linear: Mat2, color_space: ColorSpace) -> ItemEffects {
+    let texels_per_pixel = Vec2::new(linear.x_axis.length(), linear.y_axis.length()).recip();
+    let texels_per_pixel = if texels_per_pixel.is_finite() {
+        texels_per_pixel
+    } else {
+        Vec2::ZERO
+    };
+    // Thickness and blur are radii, so use the mean scale for them.
+    let texels_per_pixel_mean = (texels_per_pixel.x + texels_per_pixel.y) * 0.5;
+
+    let (outline_color, thickness) = item.outline.map_or(([0.0; 4], 0.0), |outline| {
+        (
+            tint_to_vec4(outline.color, color_space),
+            outline.thickness.max(0.0) * texels_per_pixel_mean,
+        )
+    });
+    let (shadow_color, shadow_offset, blur) =
+        item.drop_shadow
+            .map_or(([0.0; 4], Vec2::ZERO, 0.0), |shadow| {
+                let inverse = linear.inverse();
+                (
+                    tint_to_vec4(shadow.color, color_space),
+                    if inverse.is_finite() {
+                        inverse * shadow.offset
+                    } else {
+                        Vec2::ZERO
+                    },
+                    shadow.blur.max(0.0) * texels_per_pixel_mean,
+                )
+            });
+
+    ItemEffects {
+        outline_color,
+        shadow_color,
+        params: [thickness, shadow_offset.x, shadow_offset.y, blur],
+        margin: Vec2::splat(thickness)
+            .max(shadow_offset.abs() + blur)
+            .ceil(),
+    }
+}
+
+/// A pipeline that draws sprite instances for each [`AlphaMode`].
+#[derive(Debug)]
+pub(crate) struct SpritePipelines {
+    straight: wgpu::RenderPipeline,
+    premultiplied: wgpu::RenderPipeline,
+}
+
+impl SpritePipelines {
+    /// Creates pipelines that draw instances with the given fragment shader entry point.
+    fn new(
+        device: &wgpu::Device,
+        label: &str,
+        texture_format: wgpu::TextureFormat,
+        options: &RendererOptions,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        shader: &wgpu::ShaderModule,
+        fs_entry_point: &str,
+    ) -> Self {
+        Self::with_vertex(
+            device,
+            label,
+            texture_format,
+            options,
+            bind_group_layouts,
+            wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Instance::BUFFER_LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fs_entry_point,
+        )
+    }
+
+    /// Creates pipelines with the given vertex stage, and a fragment stage from the same module.
+    fn with_vertex(
+        device: &wgpu::Device,
+        label: &str,
+        texture_format: wgpu::TextureFormat,
+        options: &RendererOptions,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        vertex: wgpu::VertexState<'_>,
+        fs_entry_point: &str,
+    ) -> Self {
+        let create = |alpha_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                cache: None,
+                layout: Some(
+                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some(label),
+                        bind_group_layouts,
+                        push_constant_ranges: &[],
+                    }),
+                ),
+                vertex: vertex.clone(),
+                fragment: Some(wgpu::FragmentState {
+                    module: vertex.module,
+                    entry_point: Some(fs_entry_point),
+                    compilation_options: Default::default(),
+                    targets: &[Some(color_target_state(texture_format, alpha_mode))],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: options.depth_stencil.clone(),
+                multisample: multisample_state(options),
+                multiview: None,
+            })
+        };
+        Self {
+            straight: create(AlphaMode::Straight),
+            premultiplied: create(AlphaMode::Premultiplied),
+        }
+    }
+
+    fn get(&self, alpha_mode: AlphaMode) -> &wgpu::RenderPipeline {
+        match alpha_mode {
+            AlphaMode::Straight => &self.straight,
+            AlphaMode::Premultiplied => &self.premultiplied,
+        }
+    }
+}
+
+/// Sprites prepared for rendering to a target of a given size.
+///
+/// Batches are independent of each other, so any number of them can be prepared from the same
+/// [`Renderer`] and rendered in any order. Create each batch once with [`Renderer::create_batch`]
+/// and refill it with [`Renderer::prepare_into`].
+pub struct PreparedBatch {
+    target_uniforms_buffer: wgpu::Buffer,
+    /// Never empty, so there is always a target uniforms bind group to draw with.
+    effects_pages: Vec<EffectsPage>,
+    /// Staging source for the effects textures, which the staging belt can't write directly.
+    effects_buffer: DynamicBuffer,
+    effects_low_usage_writes: u32,
+    texture_uniforms_buffer: DynamicBuffer,
+    bindless_uniforms_buffer: DynamicBuffer,
+    lighting_uniforms_buffer: wgpu::Buffer,
+    instance_buffer: DynamicBuffer,
+    groups: Vec<PreparedGroup>,
+    culled_count: usize,
+    item_ids: Vec<(usize, usize)>,
+    staging_belt: wgpu::util::StagingBelt,
+    bind_group_cache: bind_group_cache::BindGroupCache,
+}
+
+impl PreparedBatch {
+    /// Returns the number of items skipped by [`Renderer::set_culling`] when the batch was last
+    /// prepared.
+    pub fn culled_count(&self) -> usize {
+        self.culled_count
+    }
+
+    /// Returns the group and item indices of an ID read back from a [`picking::IdBuffer`], or
+    /// `None` for 0, which marks pixels without an item.
+    pub fn item_for_id(&self, id: u32) -> Option<(usize, usize)> {
+        self.item_ids.get((id as usize).checked_sub(1)?).copied()
+    }
+}
+
+/// An effects texture and the bind group of it and the target uniforms.
+struct EffectsPage {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+#[derive(Clone)]
 struct PreparedGroup {
-    texture_bind_group: wgpu::BindGroup,
-    index_buffer_start: u32,
-    index_buffer_end: u32,
+    texture_bind_group: std::sync::Arc<wgpu::BindGroup>,
+    /// Whether `texture_bind_group` is a binding array shared by a run of groups.
+    bindless: bool,
+    lighting_bind_group: Option<std::sync::Arc<wgpu::BindGroup>>,
+    material: Option<Material>,
+    alpha_mode: AlphaMode,
+    /// Index of the effects page the instances' effects are on.
+    effects_page: usize,
+    instance_start: u32,
+    instance_end: u32,
 }
 
 impl Renderer {
-    /// Creates a new renderer.
+    /// Creates a new renderer with default options.
     pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat) -> Self {
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+        Self::with_options(device, texture_format, RendererOptions::default())
+    }
+
+    /// Creates a new renderer.
+    pub fn with_options(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        options: RendererOptions,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("spright: shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("sprite_bindings.wgsl"),
+                    include_str!("shader.wgsl")
+                )
+                .into(),
+            ),
+        });
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("spright: texture_bind_group_layout"),
@@ -164,34 +1189,408 @@ impl Renderer {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Uint,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Uint,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
                 ],
             });
 
         let target_uniforms_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("spright: target_uniforms_bind_group_layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        },
+                        count: None,
+                    },
+                ],
             });
 
-        let texture_uniforms_buffer = DynamicBuffer::new(
-            &device,
-            &wgpu::BufferDescriptor {
-                label: Some("spright: texture_uniforms_buffer"),
-                size: TextureUniforms::SHADER_SIZE.into(),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
+        let lighting_bind_group_layout = lighting::create_lighting_bind_group_layout(device);
+
+        let mut renderer = Self {
+            render_pipelines: SpritePipelines::new(
+                device,
+                "spright: render_pipeline",
+                texture_format,
+                &options,
+                &[
+                    &texture_bind_group_layout,
+                    &target_uniforms_bind_group_layout,
+                ],
+                &shader,
+                "fs_main",
+            ),
+            lit_render_pipelines: SpritePipelines::new(
+                device,
+                "spright: lit_render_pipeline",
+                texture_format,
+                &options,
+                &[
+                    &texture_bind_group_layout,
+                    &target_uniforms_bind_group_layout,
+                    &lighting_bind_group_layout,
+                ],
+                &device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("spright: lit_shader"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        concat!(
+                            include_str!("sprite_bindings.wgsl"),
+                            include_str!("shader.wgsl"),
+                            include_str!("lighting.wgsl")
+                        )
+                        .into(),
+                    ),
+                }),
+                "fs_lit",
+            ),
+            bindless: if options.bindless {
+                bindless::Bindless::new(
+                    device,
+                    texture_format,
+                    &options,
+                    &target_uniforms_bind_group_layout,
+                )
+            } else {
+                None
             },
+            texture_format,
+            sampler: device.create_sampler(&options.filtering.sampler_descriptor()),
+            options,
+            texture_bind_group_layout,
+            target_uniforms_bind_group_layout,
+            lighting_bind_group_layout,
+            dummy_texture: create_dummy_texture(device, wgpu::TextureFormat::Rgba8Unorm, 1),
+            dummy_index_texture: create_dummy_texture(device, wgpu::TextureFormat::R8Uint, 1),
+            dummy_array_texture: create_dummy_texture(device, wgpu::TextureFormat::Rgba8Unorm, 2),
+            dummy_index_array_texture: create_dummy_texture(device, wgpu::TextureFormat::R8Uint, 2),
+            registered_textures: std::collections::HashMap::new(),
+            material_pipelines: std::collections::HashMap::new(),
+            scale_factor: 1.0,
+            culling: Culling::Off,
+        };
+        renderer.register_texture(&renderer.dummy_texture.clone());
+        renderer.register_texture(&renderer.dummy_index_texture.clone());
+        renderer.register_texture(&renderer.dummy_array_texture.clone());
+        renderer.register_texture(&renderer.dummy_index_array_texture.clone());
+        renderer
+    }
+
+    /// Registers a long-lived texture, creating its views up front.
+    ///
+    /// Batches cache bind groups for every texture they draw, but still create views for
+    /// unregistered textures when a bind group is first created. The renderer keeps the texture
+    /// alive until it is unregistered.
+    pub fn register_texture(&mut self, texture: &wgpu::Texture) {
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let view = (texture.depth_or_array_layers() == 1).then(|| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                ..Default::default()
+            })
+        });
+        self.registered_textures
+            .insert(texture.clone(), RegisteredTexture { array_view, view });
+    }
+
+    /// Unregisters a texture registered with [`Renderer::register_texture`].
+    ///
+    /// Batches keep their bind groups for it until it goes unused.
+    pub fn unregister_texture(&mut self, texture: &wgpu::Texture) {
+        self.registered_textures.remove(texture);
+    }
+
+    /// Returns the texture format this renderer draws to.
+    pub fn texture_format(&self) -> wgpu::TextureFormat {
+        self.texture_format
+    }
+
+    /// Returns the number of target pixels per logical pixel.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Sets the number of target pixels per logical pixel, e.g. the window's scale factor.
+    ///
+    /// [`Item::transform`]s, outlines, drop shadows and lights are in logical pixels and scaled
+    /// to target pixels when prepared, while target sizes stay in target pixels. Defaults to 1.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// Returns which items are skipped when preparing.
+    pub fn culling(&self) -> Culling {
+        self.culling
+    }
+
+    /// Sets which items are skipped when preparing. Defaults to [`Culling::Off`].
+    ///
+    /// Culled items upload no instances. Each batch reports how many of its items were culled
+    /// with [`PreparedBatch::culled_count`].
+    pub fn set_culling(&mut self, culling: Culling) {
+        self.culling = culling;
+    }
+
+    /// Returns the options this renderer was created with.
+    pub fn options(&self) -> &RendererOptions {
+        &self.options
+    }
+
+    /// Returns a view of `texture` with `dimension`, reusing the view of a registered texture.
+    fn texture_view<'a>(
+        &'a self,
+        texture: &wgpu::Texture,
+        dimension: wgpu::TextureViewDimension,
+    ) -> std::borrow::Cow<'a, wgpu::TextureView> {
+        let registered =
+            self.registered_textures
+                .get(texture)
+                .and_then(|registered| match dimension {
+                    wgpu::TextureViewDimension::D2 => registered.view.as_ref(),
+                    _ => Some(&registered.array_view),
+                });
+        match registered {
+            Some(view) => std::borrow::Cow::Borrowed(view),
+            None => std::borrow::Cow::Owned(texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(dimension),
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Returns views of `texture` for a 2D binding and an array binding. Multi-layer textures go
+    /// in the array binding and others in the 2D one, with a dummy in the binding left over.
+    fn split_views<'a>(
+        &'a self,
+        texture: Option<&wgpu::Texture>,
+        dummy: &wgpu::Texture,
+        dummy_array: &wgpu::Texture,
+    ) -> [std::borrow::Cow<'a, wgpu::TextureView>; 2] {
+        let (texture_2d, texture_array) = match texture {
+            Some(texture) if is_layered(texture) => (dummy, texture),
+            Some(texture) => (texture, dummy_array),
+            None => (dummy, dummy_array),
+        };
+        [
+            self.texture_view(texture_2d, wgpu::TextureViewDimension::D2),
+            self.texture_view(texture_array, wgpu::TextureViewDimension::D2Array),
+        ]
+    }
+
+    /// Creates a bind group for sampling from a texture with the given texture uniforms.
+    fn create_texture_bind_group(
+        &self,
+        device: &wgpu::Device,
+        texture: &wgpu::Texture,
+        palette: Option<&wgpu::Texture>,
+        texture_uniforms: wgpu::BufferBinding,
+    ) -> wgpu::BindGroup {
+        // Integer textures can't be bound as `t`, so they get their own bindings. Each texture
+        // goes in its array or 2D binding, and a dummy in the other.
+        let (texture, index_texture) = if texture.format() == wgpu::TextureFormat::R8Uint {
+            (None, Some(texture))
+        } else {
+            (Some(texture), None)
+        };
+        let [texture_2d_view, texture_view] =
+            self.split_views(texture, &self.dummy_texture, &self.dummy_array_texture);
+        let [index_2d_view, index_view] = self.split_views(
+            index_texture,
+            &self.dummy_index_texture,
+            &self.dummy_index_array_texture,
         );
+        let [palette_view, palette_array_view] =
+            self.split_views(palette, &self.dummy_texture, &self.dummy_array_texture);
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: texture_bind_group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(texture_uniforms),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&palette_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&index_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&texture_2d_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::TextureView(&index_2d_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: wgpu::BindingResource::TextureView(&palette_array_view),
+                },
+            ],
+        })
+    }
+
+    /// Creates a bind group for sampling from a run's textures, padded with the dummy texture.
+    fn create_bindless_bind_group(
+        &self,
+        device: &wgpu::Device,
+        bindless: &bindless::Bindless,
+        run: &bindless::Run<'_>,
+        texture_uniforms: wgpu::BufferBinding,
+    ) -> wgpu::BindGroup {
+        let views = run
+            .slots
+            .iter()
+            .map(|&(texture, _)| texture)
+            .chain(std::iter::repeat(&self.dummy_array_texture))
+            .take(bindless.max_textures as usize)
+            .map(|texture| self.texture_view(texture, wgpu::TextureViewDimension::D2Array))
+            .collect::<Vec<_>>();
+        let texture_views = views.iter().map(|view| &**view).collect::<Vec<_>>();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: bindless_bind_group"),
+            layout: &bindless.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureViewArray(&texture_views),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(texture_uniforms),
+                },
+            ],
+        })
+    }
+
+    /// Creates a bind group of target uniforms and an effects texture, as drawn by every sprite
+    /// pipeline.
+    pub(crate) fn create_target_uniforms_bind_group(
+        &self,
+        device: &wgpu::Device,
+        target_uniforms_buffer: &wgpu::Buffer,
+        effects_texture: &wgpu::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: target_uniforms_bind_group"),
+            layout: &self.target_uniforms_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: target_uniforms_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &effects_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        })
+    }
 
+    fn create_effects_page(
+        &self,
+        device: &wgpu::Device,
+        target_uniforms_buffer: &wgpu::Buffer,
+        rows: u32,
+    ) -> EffectsPage {
+        let texture = create_effects_texture(device, rows);
+        EffectsPage {
+            bind_group: self.create_target_uniforms_bind_group(
+                device,
+                target_uniforms_buffer,
+                &texture,
+            ),
+            texture,
+        }
+    }
+
+    /// Creates an empty batch.
+    pub fn create_batch(&self, device: &wgpu::Device) -> PreparedBatch {
         let target_uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("spright: target_uniforms_buffer"),
             size: TargetUniforms::SHADER_SIZE.into(),
@@ -199,264 +1598,983 @@ impl Renderer {
             mapped_at_creation: false,
         });
 
-        let target_uniforms_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("spright: target_uniforms_bind_group"),
-            layout: &target_uniforms_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: target_uniforms_buffer.as_entire_binding(),
-            }],
-        });
-
-        let vertex_buffer = DynamicBuffer::new(
-            &device,
-            &wgpu::BufferDescriptor {
-                label: Some("spright: vertex_buffer"),
-                size: std::mem::size_of::<Vertex>() as u64 * 1024,
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            },
-        );
-
-        let index_buffer = DynamicBuffer::new(
-            &device,
-            &wgpu::BufferDescriptor {
-                label: Some("spright: vertex_buffer"),
-                size: std::mem::size_of::<u32>() as u64 * 1024,
-                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            },
-        );
+        let effects_page = self.create_effects_page(device, &target_uniforms_buffer, 1);
 
-        Self {
-            render_pipeline: device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("spright: render_pipeline"),
-                cache: None,
-                layout: Some(
-                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                        label: Some("spright: render_pipeline.layout"),
-                        bind_group_layouts: &[
-                            &texture_bind_group_layout,
-                            &target_uniforms_bind_group_layout,
-                        ],
-                        push_constant_ranges: &[],
-                    }),
-                ),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    buffers: &[Vertex::BUFFER_LAYOUT],
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    compilation_options: Default::default(),
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: texture_format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::all(),
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            }),
-            texture_bind_group_layout,
+        PreparedBatch {
             target_uniforms_buffer,
-            target_uniforms_bind_group,
-            texture_uniforms_buffer,
-            vertex_buffer,
-            index_buffer,
-            prepared_groups: vec![],
-            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                ..Default::default()
-            }),
+            effects_pages: vec![effects_page],
+            effects_buffer: DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: effects_buffer"),
+                    size: 0,
+                    usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            effects_low_usage_writes: 0,
+            lighting_uniforms_buffer: device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("spright: lighting_uniforms_buffer"),
+                    contents: &{
+                        let mut buffer = UniformBuffer::new(vec![]);
+                        buffer
+                            .write(&lighting::lighting_uniforms(
+                                &lighting::Lighting::default(),
+                                self.tint_color_space(),
+                            ))
+                            .unwrap();
+                        buffer.into_inner()
+                    },
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                },
+            ),
+            texture_uniforms_buffer: DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: texture_uniforms_buffer"),
+                    size: TextureUniforms::SHADER_SIZE.into(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            bindless_uniforms_buffer: DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: bindless_uniforms_buffer"),
+                    size: TextureUniforms::SHADER_SIZE.into(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            instance_buffer: DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: instance_buffer"),
+                    size: std::mem::size_of::<Instance>() as u64 * 1024,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            groups: vec![],
+            culled_count: 0,
+            item_ids: vec![],
+            staging_belt: wgpu::util::StagingBelt::new(
+                self.options.buffer_capacity.staging_chunk_size,
+            ),
+            bind_group_cache: bind_group_cache::BindGroupCache::default(),
         }
     }
 
+    /// Prepares sprites for rendering into a new batch.
+    ///
+    /// Every call allocates a batch from scratch, so this suits one-off batches. Batches drawn
+    /// every frame should be created once with [`Renderer::create_batch`] and refilled with
+    /// [`Renderer::prepare_into`].
     pub fn prepare(
-        &mut self,
+        &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         target_size: wgpu::Extent3d,
         groups: &[Group<'_>],
+    ) -> PreparedBatch {
+        let mut batch = self.create_batch(device);
+        self.prepare_into(device, queue, &mut batch, target_size, groups);
+        batch
+    }
+
+    /// Prepares sprites for rendering into an existing batch, reusing its buffers.
+    ///
+    /// This is the usual way to prepare sprites: the batch's buffers and textures keep the size
+    /// they grew to, and its bind groups are reused for the textures drawn again. `batch` must
+    /// have been created by this renderer.
+    pub fn prepare_into(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batch: &mut PreparedBatch,
+        target_size: wgpu::Extent3d,
+        groups: &[Group<'_>],
     ) {
-        queue.write_buffer(&self.target_uniforms_buffer, 0, &{
+        queue.write_buffer(&batch.target_uniforms_buffer, 0, &{
             let mut buffer = UniformBuffer::new(vec![]);
-            buffer
-                .write(&TargetUniforms {
-                    size: Vec3 {
-                        x: target_size.width as f32,
-                        y: target_size.height as f32,
-                        z: 0.0,
-                    },
-                })
-                .unwrap();
+            buffer.write(&target_uniforms(target_size)).unwrap();
             buffer.into_inner()
         });
 
-        self.prepared_groups.clear();
+        batch.groups.clear();
 
-        let min_uniform_buffer_offset_alignment =
-            device.limits().min_uniform_buffer_offset_alignment;
+        let texture_uniforms_stride = TextureUniforms::SHADER_SIZE
+            .get()
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
 
-        let mut texture_uniforms_buffer = DynamicUniformBuffer::new_with_alignment(
-            vec![],
-            min_uniform_buffer_offset_alignment as u64,
-        );
+        let mut texture_uniforms_buffer =
+            DynamicUniformBuffer::new_with_alignment(vec![], texture_uniforms_stride);
 
-        for group in groups {
-            texture_uniforms_buffer
-                .write(&TextureUniforms {
-                    size: Vec3 {
-                        x: group.texture.width() as f32,
-                        y: group.texture.height() as f32,
-                        z: 0.0,
-                    },
-                    is_mask: (group.texture.format() == wgpu::TextureFormat::R8Unorm) as u32,
+        let (runs, texture_indices) = match &self.bindless {
+            Some(bindless) => {
+                bindless::plan_runs(groups, bindless.max_textures as usize, |group| {
+                    self.alpha_mode(group)
                 })
-                .unwrap();
-        }
-
-        self.texture_uniforms_buffer
-            .write(device, queue, &texture_uniforms_buffer.into_inner());
-
-        let mut vertices = vec![];
-        let mut indices = vec![];
-
-        for (i, group) in groups.into_iter().enumerate() {
-            let index_buffer_start = indices.len() as u32;
-
-            for item in group.items.iter() {
-                let offset = vertices.len() as u32;
-
-                let tint = [
-                    item.tint.r as f32 / 255.0,
-                    item.tint.g as f32 / 255.0,
-                    item.tint.b as f32 / 255.0,
-                    item.tint.a as f32 / 255.0,
-                ];
-
-                let left = item.src_offset.x;
-                let top = item.src_offset.y;
-                let right = item.src_offset.x + item.src_size.x as i32;
-                let bottom = item.src_offset.y + item.src_size.y as i32;
-
-                vertices.extend([
-                    Vertex {
-                        position: item
-                            .transform
-                            .transform_point2(Vec2::new(0.0, 0.0))
-                            .extend(0.0)
-                            .to_array(),
-                        tex_coords: [left as f32, top as f32],
-                        layer: item.src_layer,
-                        tint,
-                    },
-                    Vertex {
-                        position: item
-                            .transform
-                            .transform_point2(Vec2::new(0.0, item.src_size.y as f32))
-                            .extend(0.0)
-                            .to_array(),
-                        tex_coords: [left as f32, bottom as f32],
-                        layer: item.src_layer,
-                        tint,
-                    },
-                    Vertex {
-                        position: item
-                            .transform
-                            .transform_point2(Vec2::new(item.src_size.x as f32, 0.0))
-                            .extend(0.0)
-                            .to_array(),
-                        tex_coords: [right as f32, top as f32],
-                        layer: item.src_layer,
-                        tint,
-                    },
-                    Vertex {
-                        position: item
-                            .transform
-                            .transform_point2(Vec2::new(
-                                item.src_size.x as f32,
-                                item.src_size.y as f32,
-                            ))
-                            .extend(0.0)
-                            .to_array(),
-                        tex_coords: [right as f32, bottom as f32],
-                        layer: item.src_layer,
-                        tint,
-                    },
-                ]);
-
-                indices.extend(
-                    [
-                        0, 1, 2, //
-                        1, 2, 3,
-                    ]
-                    .map(|v| v + offset),
-                );
             }
+            None => (vec![], vec![0; groups.len()]),
+        };
+        let mut in_run = vec![false; groups.len()];
+        for run in runs.iter() {
+            in_run[run.groups.clone()].fill(true);
+        }
 
-            self.prepared_groups.push(PreparedGroup {
-                texture_bind_group: device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: Some("spright: texture_bind_group"),
-                    layout: &self.texture_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(
-                                &group.texture.create_view(&wgpu::TextureViewDescriptor {
-                                    dimension: Some(wgpu::TextureViewDimension::D2Array),
-                                    ..Default::default()
-                                }),
-                            ),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(&self.sampler),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                                buffer: &self.texture_uniforms_buffer,
-                                offset: (i * min_uniform_buffer_offset_alignment as usize) as u64,
-                                size: Some(TextureUniforms::SHADER_SIZE),
-                            }),
+        // Groups with the same uniforms share a slot, and so a bind group. Groups in runs have
+        // theirs in the run's uniforms instead.
+        // Textures hash by identity, which their interior mutability doesn't affect.
+        #[allow(clippy::mutable_key_type)]
+        let mut uniforms_offsets = std::collections::HashMap::new();
+        let group_uniforms_offsets = groups
+            .iter()
+            .zip(in_run.iter())
+            .map(|(group, &in_run)| {
+                if in_run {
+                    return None;
+                }
+                let alpha_mode = self.alpha_mode(group);
+                Some(
+                    *uniforms_offsets
+                        .entry((
+                            group.texture,
+                            group.palette,
+                            group.lod_bias.to_bits(),
+                            alpha_mode,
+                        ))
+                        .or_insert_with(|| {
+                            texture_uniforms_buffer
+                                .write(&texture_uniforms(
+                                    group.texture,
+                                    group.palette,
+                                    group.lod_bias,
+                                    alpha_mode,
+                                ))
+                                .unwrap()
+                        }),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Each run's uniforms are an array indexed by texture index, padded to the array size.
+        let bindless_uniforms_stride = self.bindless.as_ref().map_or(0, |bindless| {
+            bindless
+                .uniforms_size()
+                .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64)
+        });
+        let mut bindless_uniforms_buffer =
+            DynamicUniformBuffer::new_with_alignment(vec![], TextureUniforms::SHADER_SIZE.get());
+        let max_textures = self
+            .bindless
+            .as_ref()
+            .map_or(0, |bindless| bindless.max_textures as usize);
+        for (i, run) in runs.iter().enumerate() {
+            bindless_uniforms_buffer.set_offset(i as u64 * bindless_uniforms_stride);
+            for slot in 0..max_textures {
+                let (texture, lod_bias) = run
+                    .slots
+                    .get(slot)
+                    .copied()
+                    .unwrap_or((&self.dummy_texture, 0.0));
+                bindless_uniforms_buffer
+                    .write(&texture_uniforms(texture, None, lod_bias, run.alpha_mode))
+                    .unwrap();
+            }
+        }
+
+        let capacity = &self.options.buffer_capacity;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("spright: prepare_encoder"),
+        });
+        let texture_uniforms_reallocated = batch.texture_uniforms_buffer.write_staged(
+            device,
+            &mut encoder,
+            &mut batch.staging_belt,
+            &texture_uniforms_buffer.into_inner(),
+            capacity,
+        );
+        let bindless_uniforms_reallocated = batch.bindless_uniforms_buffer.write_staged(
+            device,
+            &mut encoder,
+            &mut batch.staging_belt,
+            &bindless_uniforms_buffer.into_inner(),
+            capacity,
+        );
+        if texture_uniforms_reallocated || bindless_uniforms_reallocated {
+            batch.bind_group_cache.clear();
+        }
+        batch.bind_group_cache.begin();
+
+        let tint_color_space = self.tint_color_space();
+        let cull_rect = match self.culling {
+            Culling::Off => None,
+            Culling::Target => Some((
+                Vec2::ZERO,
+                Vec2::new(target_size.width as f32, target_size.height as f32),
+            )),
+            Culling::View { min, max } => Some((min * self.scale_factor, max * self.scale_factor)),
+        };
+        let mut geometry =
+            self.build_geometry(groups, &texture_indices, cull_rect, tint_color_space, true);
+        batch.culled_count = geometry.culled_count;
+        batch.item_ids = geometry.item_ids;
+
+        let mut runs = runs.iter().enumerate().peekable();
+        for (i, (group, uniforms_offset)) in groups.iter().zip(group_uniforms_offsets).enumerate() {
+            if let Some((j, run)) = runs.next_if(|(_, run)| run.groups.start == i) {
+                // Runs are only planned with a binding array.
+                let bindless = self.bindless.as_ref().unwrap();
+                let uniforms_offset = j as u64 * bindless_uniforms_stride;
+                let key = bind_group_cache::BindGroupKey::Bindless {
+                    textures: run
+                        .slots
+                        .iter()
+                        .map(|&(texture, _)| texture.clone())
+                        .collect(),
+                    sampler: self.sampler.clone(),
+                    uniforms_offset,
+                };
+                let bindless_uniforms_buffer = &batch.bindless_uniforms_buffer;
+                // Geometry is in group order, so a run's instances are contiguous.
+                batch.groups.push(PreparedGroup {
+                    texture_bind_group: batch.bind_group_cache.get_or_insert_with(key, || {
+                        self.create_bindless_bind_group(
+                            device,
+                            bindless,
+                            run,
+                            wgpu::BufferBinding {
+                                buffer: bindless_uniforms_buffer,
+                                offset: uniforms_offset,
+                                size: wgpu::BufferSize::new(bindless.uniforms_size()),
+                            },
+                        )
+                    }),
+                    bindless: true,
+                    lighting_bind_group: None,
+                    material: None,
+                    alpha_mode: run.alpha_mode,
+                    effects_page: 0,
+                    instance_start: geometry.instance_ranges[run.groups.start].start,
+                    instance_end: geometry.instance_ranges[run.groups.end - 1].end,
+                });
+            }
+            let Some(uniforms_offset) = uniforms_offset else {
+                continue;
+            };
+            let instance_range = geometry.instance_ranges[i].clone();
+
+            let key = bind_group_cache::BindGroupKey::Texture {
+                texture: group.texture.clone(),
+                palette: group.palette.cloned(),
+                sampler: self.sampler.clone(),
+                uniforms_offset,
+            };
+            let texture_uniforms_buffer = &batch.texture_uniforms_buffer;
+            batch.groups.push(PreparedGroup {
+                texture_bind_group: batch.bind_group_cache.get_or_insert_with(key, || {
+                    self.create_texture_bind_group(
+                        device,
+                        group.texture,
+                        group.palette,
+                        wgpu::BufferBinding {
+                            buffer: texture_uniforms_buffer,
+                            offset: uniforms_offset,
+                            size: Some(TextureUniforms::SHADER_SIZE),
                         },
-                    ],
+                    )
+                }),
+                bindless: false,
+                lighting_bind_group: group.normal_map.map(|normal_map| {
+                    let key = bind_group_cache::BindGroupKey::Lighting {
+                        normal_map: normal_map.clone(),
+                    };
+                    batch.bind_group_cache.get_or_insert_with(key, || {
+                        let [normal_map_view, normal_map_array_view] = self.split_views(
+                            Some(normal_map),
+                            &self.dummy_texture,
+                            &self.dummy_array_texture,
+                        );
+                        device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: Some("spright: lighting_bind_group"),
+                            layout: &self.lighting_bind_group_layout,
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 0,
+                                    resource: batch.lighting_uniforms_buffer.as_entire_binding(),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::TextureView(
+                                        &normal_map_array_view,
+                                    ),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 2,
+                                    resource: wgpu::BindingResource::TextureView(&normal_map_view),
+                                },
+                            ],
+                        })
+                    })
                 }),
-                index_buffer_start,
-                index_buffer_end: indices.len() as u32,
+                material: group.material.cloned(),
+                alpha_mode: self.alpha_mode(group),
+                effects_page: 0,
+                instance_start: instance_range.start,
+                instance_end: instance_range.end,
             });
         }
 
-        self.vertex_buffer
-            .write(device, queue, bytemuck::cast_slice(&vertices[..]));
-        self.index_buffer
-            .write(device, queue, bytemuck::cast_slice(&indices[..]));
-    }
-
-    /// Renders prepared sprites.
-    pub fn render<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
-        rpass.set_pipeline(&self.render_pipeline);
-        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        rpass.set_bind_group(1, &self.target_uniforms_bind_group, &[]);
-        for prepared_group in self.prepared_groups.iter() {
-            rpass.set_bind_group(0, &prepared_group.texture_bind_group, &[]);
-            rpass.draw_indexed(
-                prepared_group.index_buffer_start..prepared_group.index_buffer_end,
-                0,
-                0..1,
+        let effects_per_page = effects_per_page(device);
+        if geometry.effects.len() > effects_per_page as usize {
+            let groups = std::mem::take(&mut batch.groups);
+            batch.groups = split_effects_pages(groups, &mut geometry.instances, effects_per_page);
+        }
+
+        batch.instance_buffer.write_staged(
+            device,
+            &mut encoder,
+            &mut batch.staging_belt,
+            bytemuck::cast_slice(&geometry.instances[..]),
+            capacity,
+        );
+        self.write_effects(device, &mut encoder, batch, &geometry.effects);
+
+        batch.bind_group_cache.evict();
+
+        batch.staging_belt.finish();
+        queue.submit([encoder.finish()]);
+        batch.staging_belt.recall();
+    }
+
+    /// Records an upload of the effects of a batch's items through its staging belt.
+    ///
+    /// Effects past what one texture can hold go on further pages. Every page but the last is
+    /// full, and the last grows and shrinks as the batch's [`BufferCapacity`] allows.
+    fn write_effects(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        batch: &mut PreparedBatch,
+        effects: &[Effects],
+    ) {
+        let per_row = EFFECTS_TEXTURE_WIDTH / EFFECTS_TEXELS;
+        let row_size = EFFECTS_TEXTURE_WIDTH as wgpu::BufferAddress * 16;
+        let max_rows = device.limits().max_texture_dimension_2d;
+        let rows = (effects.len() as u32).div_ceil(per_row);
+        let page_count = rows.div_ceil(max_rows).max(1) as usize;
+        let last_rows = rows - (page_count as u32 - 1) * max_rows;
+
+        batch.effects_pages.truncate(page_count);
+        for (i, page) in batch.effects_pages.iter_mut().enumerate() {
+            if i + 1 < page_count && page.texture.height() < max_rows {
+                *page = self.create_effects_page(device, &batch.target_uniforms_buffer, max_rows);
+            }
+        }
+        while batch.effects_pages.len() < page_count {
+            let page_rows = if batch.effects_pages.len() + 1 < page_count {
+                max_rows
+            } else {
+                last_rows
+            };
+            let page = self.create_effects_page(device, &batch.target_uniforms_buffer, page_rows);
+            batch.effects_pages.push(page);
+        }
+        let last = batch.effects_pages.last_mut().unwrap();
+        if let Some(size) = self.options.buffer_capacity.resized(
+            last.texture.height() as wgpu::BufferAddress * row_size,
+            last_rows as wgpu::BufferAddress * row_size,
+            &mut batch.effects_low_usage_writes,
+        ) {
+            let page_rows = (size.div_ceil(row_size) as u32).clamp(last_rows.max(1), max_rows);
+            *last = self.create_effects_page(device, &batch.target_uniforms_buffer, page_rows);
+        }
+        if rows == 0 {
+            return;
+        }
+
+        // Pad the last row, so the upload covers whole rows.
+        let mut data = effects.to_vec();
+        data.resize((rows * per_row) as usize, Effects::zeroed());
+        batch.effects_buffer.write_staged(
+            device,
+            encoder,
+            &mut batch.staging_belt,
+            bytemuck::cast_slice(&data),
+            &self.options.buffer_capacity,
+        );
+        for (i, page) in batch.effects_pages.iter().enumerate() {
+            let page_start = i as u32 * max_rows;
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &batch.effects_buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: page_start as wgpu::BufferAddress * row_size,
+                        bytes_per_row: Some(row_size as u32),
+                        rows_per_image: None,
+                    },
+                },
+                page.texture.as_image_copy(),
+                wgpu::Extent3d {
+                    width: EFFECTS_TEXTURE_WIDTH,
+                    height: (rows - page_start).min(max_rows),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+    }
+
+    /// Builds the instances of every unculled item, and the effects of those that have any.
+    ///
+    /// Each group gets room for all of its items up front, so groups are written independently,
+    /// in parallel if `parallel` is set and the `rayon` feature is enabled. The output is the same
+    /// either way.
+    fn build_geometry(
+        &self,
+        groups: &[Group<'_>],
+        texture_indices: &[u32],
+        cull_rect: Option<(Vec2, Vec2)>,
+        tint_color_space: ColorSpace,
+        parallel: bool,
+    ) -> Geometry {
+        let item_count = groups.iter().map(|group| group.items.len()).sum::<usize>();
+        let mut instances = vec![Instance::zeroed(); item_count];
+        let mut item_indices = vec![0; item_count];
+
+        let mut chunks = Vec::with_capacity(groups.len());
+        let (mut instances_rest, mut item_indices_rest) =
+            (&mut instances[..], &mut item_indices[..]);
+        for (group, &texture_index) in groups.iter().zip(texture_indices) {
+            let (group_instances, rest) = instances_rest.split_at_mut(group.items.len());
+            instances_rest = rest;
+            let (group_item_indices, rest) = item_indices_rest.split_at_mut(group.items.len());
+            item_indices_rest = rest;
+            chunks.push((group, texture_index, group_instances, group_item_indices));
+        }
+
+        // Each group numbers its effects from 0, and they're offset once the groups are joined.
+        let outputs = map_groups(
+            chunks,
+            parallel,
+            |(group, texture_index, instances, item_indices)| {
+                let texel_scale = self.texel_density(group).recip();
+                let mut count = 0;
+                let mut effects = vec![];
+                for (j, item) in group.items.iter().enumerate() {
+                    let (instance, instance_effects) =
+                        self.item_instance(item, texel_scale, tint_color_space);
+
+                    if let Some((min, max)) = cull_rect {
+                        let corners = instance.corners.map(Vec2::from);
+                        let item_min = corners.into_iter().reduce(Vec2::min).unwrap();
+                        let item_max = corners.into_iter().reduce(Vec2::max).unwrap();
+                        if item_max.cmplt(min).any() || item_min.cmpgt(max).any() {
+                            continue;
+                        }
+                    }
+
+                    instances[count] = Instance {
+                        texture_index,
+                        effects_index: instance_effects.map_or(NO_EFFECTS, |instance_effects| {
+                            effects.push(instance_effects);
+                            effects.len() as u32 - 1
+                        }),
+                        ..instance
+                    };
+                    item_indices[count] = j;
+                    count += 1;
+                }
+                (count, effects)
+            },
+        );
+
+        // Close the gaps left by culled items.
+        let mut effects = vec![];
+        let mut item_ids = Vec::with_capacity(item_count);
+        let mut instance_ranges = Vec::with_capacity(groups.len());
+        let mut group_start = 0;
+        let mut visible_start = 0;
+        for (i, (group, (count, group_effects))) in groups.iter().zip(outputs).enumerate() {
+            instances.copy_within(group_start..group_start + count, visible_start);
+            let effects_start = effects.len() as u32;
+            for instance in instances[visible_start..visible_start + count].iter_mut() {
+                if instance.effects_index != NO_EFFECTS {
+                    instance.effects_index += effects_start;
+                }
+            }
+            effects.extend(group_effects);
+            item_ids.extend(
+                item_indices[group_start..group_start + count]
+                    .iter()
+                    .map(|&j| (i, j)),
             );
+            instance_ranges.push(visible_start as u32..(visible_start + count) as u32);
+            group_start += group.items.len();
+            visible_start += count;
+        }
+        instances.truncate(visible_start);
+
+        Geometry {
+            instances,
+            effects,
+            instance_ranges,
+            item_ids,
+            culled_count: item_count - visible_start,
         }
     }
+
+    fn tint_color_space(&self) -> ColorSpace {
+        let is_float = matches!(
+            self.texture_format,
+            wgpu::TextureFormat::Rgba16Float
+                | wgpu::TextureFormat::Rgba32Float
+                | wgpu::TextureFormat::Rg11b10Ufloat
+        );
+        self.options
+            .tint_color_space
+            .unwrap_or(if self.texture_format.is_srgb() || is_float {
+                ColorSpace::Linear
+            } else {
+                ColorSpace::Srgb
+            })
+    }
+
+    fn alpha_mode(&self, group: &Group<'_>) -> AlphaMode {
+        group.alpha_mode.unwrap_or(self.options.alpha_mode)
+    }
+
+    /// Returns an item's instance, with corners in target pixels, and its effects if it has any.
+    fn item_instance(
+        &self,
+        item: &Item,
+        texel_scale: f32,
+        tint_color_space: ColorSpace,
+    ) -> (Instance, Option<Effects>) {
+        let src_offset = item.src_offset.as_vec2();
+        let src_size = item.src_size.as_vec2();
+        let effects = item_effects(item, item.transform.matrix2 * texel_scale, tint_color_space);
+        // Grow the quad by the effect margin so the effects aren't clipped.
+        let (local_min, local_max) = (-effects.margin, src_size + effects.margin);
+        let instance = Instance {
+            corners: [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(0.0, 1.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0),
+            ]
+            .map(|corner| {
+                let local = local_min + corner * (local_max - local_min);
+                self.options
+                    .pixel_snap
+                    .snap(item.transform.transform_point2(local * texel_scale) * self.scale_factor)
+                    .to_array()
+            }),
+            tex_rect: [
+                src_offset.x + local_min.x,
+                src_offset.y + local_min.y,
+                src_offset.x + local_max.x,
+                src_offset.y + local_max.y,
+            ],
+            src_rect: [
+                src_offset.x,
+                src_offset.y,
+                src_offset.x + src_size.x,
+                src_offset.y + src_size.y,
+            ],
+            tint: tint_to_vec4(item.tint, tint_color_space),
+            layer: item.src_layer,
+            palette_row: item.palette_row,
+            texture_index: 0,
+            effects_index: NO_EFFECTS,
+        };
+
+        let has_effects = item.color_matrix.is_some()
+            || item.color_offset != LinearColor::default()
+            || item.outline.is_some()
+            || item.drop_shadow.is_some();
+        let effects = has_effects.then(|| {
+            let [c0, c1, c2, c3, c4] = item.color_matrix.unwrap_or_default().columns();
+            let color_offset = tint_to_vec4(item.color_offset, tint_color_space);
+            Effects {
+                color_matrix: [c0, c1, c2, c3],
+                color_offset: (Vec4::from(c4) + Vec4::from(color_offset)).to_array(),
+                outline_color: effects.outline_color,
+                shadow_color: effects.shadow_color,
+                params: effects.params,
+            }
+        });
+        (instance, effects)
+    }
+
+    fn texel_density(&self, group: &Group<'_>) -> f32 {
+        group.texel_density.unwrap_or_else(|| {
+            if is_mask(group.texture, group.palette) {
+                self.scale_factor
+            } else {
+                1.0
+            }
+        })
+    }
+
+    /// Renders a prepared batch.
+    pub fn render<'rpass>(
+        &'rpass self,
+        rpass: &mut wgpu::RenderPass<'rpass>,
+        batch: &'rpass PreparedBatch,
+    ) {
+        rpass.set_vertex_buffer(0, batch.instance_buffer.slice(..));
+        for prepared_group in batch.groups.iter() {
+            rpass.set_bind_group(
+                1,
+                &batch.effects_pages[prepared_group.effects_page].bind_group,
+                &[],
+            );
+            if let Some(material) = &prepared_group.material {
+                rpass.set_pipeline(
+                    material
+                        .pipeline
+                        .render_pipelines
+                        .get(prepared_group.alpha_mode),
+                );
+                rpass.set_bind_group(2, material.bind_group.as_ref(), &[]);
+            } else if let Some(lighting_bind_group) = &prepared_group.lighting_bind_group {
+                rpass.set_pipeline(self.lit_render_pipelines.get(prepared_group.alpha_mode));
+                rpass.set_bind_group(2, lighting_bind_group.as_ref(), &[]);
+            } else if let Some(bindless) =
+                self.bindless.as_ref().filter(|_| prepared_group.bindless)
+            {
+                rpass.set_pipeline(bindless.render_pipelines.get(prepared_group.alpha_mode));
+            } else {
+                rpass.set_pipeline(self.render_pipelines.get(prepared_group.alpha_mode));
+            }
+            rpass.set_bind_group(0, prepared_group.texture_bind_group.as_ref(), &[]);
+            rpass.draw(
+                0..4,
+                prepared_group.instance_start..prepared_group.instance_end,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_grow_geometrically() {
+        let capacity = BufferCapacity {
+            growth_factor: 2.0,
+            min_size: 64,
+            ..Default::default()
+        };
+        let mut low_usage_writes = 0;
+
+        // Growth is at least the minimum size, then doubles.
+        assert_eq!(capacity.resized(16, 32, &mut low_usage_writes), Some(64));
+        assert_eq!(capacity.resized(64, 65, &mut low_usage_writes), Some(128));
+        assert_eq!(capacity.resized(128, 128, &mut low_usage_writes), None);
+        // Data larger than the grown size gets exactly what it needs.
+        assert_eq!(
+            capacity.resized(128, 1000, &mut low_usage_writes),
+            Some(1000)
+        );
+
+        // Growth factors below 1 never shrink on growth.
+        let capacity = BufferCapacity {
+            growth_factor: 0.5,
+            min_size: 0,
+            ..capacity
+        };
+        assert_eq!(capacity.resized(100, 101, &mut low_usage_writes), Some(101));
+    }
+
+    #[test]
+    fn buffers_shrink_after_sustained_low_usage() {
+        let capacity = BufferCapacity {
+            growth_factor: 2.0,
+            min_size: 64,
+            shrink_after: Some(3),
+            shrink_threshold: 0.25,
+            ..Default::default()
+        };
+        let mut low_usage_writes = 0;
+
+        assert_eq!(capacity.resized(4096, 100, &mut low_usage_writes), None);
+        assert_eq!(capacity.resized(4096, 100, &mut low_usage_writes), None);
+        // A write above the threshold resets the count.
+        assert_eq!(capacity.resized(4096, 2048, &mut low_usage_writes), None);
+        assert_eq!(low_usage_writes, 0);
+        assert_eq!(capacity.resized(4096, 100, &mut low_usage_writes), None);
+        assert_eq!(capacity.resized(4096, 100, &mut low_usage_writes), None);
+        // Shrinks to the grown size of the data, leaving room to grow again.
+        assert_eq!(
+            capacity.resized(4096, 100, &mut low_usage_writes),
+            Some(200)
+        );
+        assert_eq!(low_usage_writes, 0);
+
+        // Never shrinks below the minimum size, or without shrink_after.
+        let mut low_usage_writes = 0;
+        for _ in 0..3 {
+            assert_eq!(capacity.resized(64, 1, &mut low_usage_writes), None);
+        }
+        let capacity = BufferCapacity {
+            shrink_after: None,
+            ..capacity
+        };
+        for _ in 0..10 {
+            assert_eq!(capacity.resized(4096, 1, &mut low_usage_writes), None);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_geometry_matches_serial() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let renderer = Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let textures = [
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::R8Unorm,
+        ]
+        .map(|format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 16,
+                    height: 16,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        });
+
+        let groups = (0..50)
+            .map(|i| {
+                Group::new(
+                    &textures[i % 2],
+                    (0..i * 7)
+                        .map(|j| Item {
+                            src_offset: IVec2::new(j as i32 % 8, 0),
+                            src_size: UVec2::new(8, 8),
+                            transform: Affine2::from_angle_translation(
+                                j as f32 * 0.1,
+                                Vec2::new((j * 13 % 700) as f32 - 100.0, (i * 11) as f32),
+                            ),
+                            outline: (j % 3 == 0).then_some(Outline {
+                                color: LinearColor::WHITE,
+                                thickness: 1.0,
+                            }),
+                            ..Default::default()
+                        })
+                        .collect(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for cull_rect in [None, Some((Vec2::ZERO, Vec2::new(400.0, 300.0)))] {
+            let texture_indices = (0..groups.len() as u32).collect::<Vec<_>>();
+            let serial = renderer.build_geometry(
+                &groups,
+                &texture_indices,
+                cull_rect,
+                ColorSpace::Srgb,
+                false,
+            );
+            let parallel = renderer.build_geometry(
+                &groups,
+                &texture_indices,
+                cull_rect,
+                ColorSpace::Srgb,
+                true,
+            );
+            assert_eq!(
+                bytemuck::cast_slice::<_, u8>(&serial.instances),
+                bytemuck::cast_slice::<_, u8>(&parallel.instances)
+            );
+            assert_eq!(
+                bytemuck::cast_slice::<_, u8>(&serial.effects),
+                bytemuck::cast_slice::<_, u8>(&parallel.effects)
+            );
+            assert_eq!(serial.instance_ranges, parallel.instance_ranges);
+            assert_eq!(serial.item_ids, parallel.item_ids);
+            assert_eq!(serial.culled_count, parallel.culled_count);
+            assert_eq!(cull_rect.is_some(), serial.culled_count > 0);
+        }
+    }
+
+    #[test]
+    fn only_items_with_effects_store_them() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let renderer = Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = create_dummy_texture(&device, wgpu::TextureFormat::Rgba8Unorm, 1);
+        let items = vec![
+            Item::default(),
+            Item {
+                outline: Some(Outline {
+                    color: LinearColor::WHITE,
+                    thickness: 1.0,
+                }),
+                ..Default::default()
+            },
+            Item::default(),
+            Item {
+                color_offset: LinearColor::new(1.0, 1.0, 1.0, 0.0),
+                ..Default::default()
+            },
+        ];
+        let groups = [
+            Group::new(&texture, items.clone()),
+            Group::new(&texture, items),
+        ];
+
+        let geometry = renderer.build_geometry(&groups, &[0, 0], None, ColorSpace::Linear, false);
+        assert_eq!(
+            geometry
+                .instances
+                .iter()
+                .map(|instance| instance.effects_index)
+                .collect::<Vec<_>>(),
+            [NO_EFFECTS, 0, NO_EFFECTS, 1, NO_EFFECTS, 2, NO_EFFECTS, 3]
+        );
+        assert_eq!(geometry.effects.len(), 4);
+        // The color offset is folded into the identity matrix's zero translation.
+        assert_eq!(geometry.effects[1].color_offset, [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(geometry.effects[1].params, [0.0; 4]);
+    }
+
+    #[test]
+    fn draws_split_where_effects_continue_on_the_next_page() {
+        let Some((device, _queue)) = test_device() else {
+            return;
+        };
+
+        let renderer = Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = create_dummy_texture(&device, wgpu::TextureFormat::Rgba8Unorm, 1);
+        let outlined = Item {
+            outline: Some(Outline {
+                color: LinearColor::WHITE,
+                thickness: 1.0,
+            }),
+            ..Default::default()
+        };
+        let items = vec![Item::default(), outlined.clone(), Item::default(), outlined];
+        let groups = [
+            Group::new(&texture, items.clone()),
+            Group::new(&texture, items),
+        ];
+        let mut geometry =
+            renderer.build_geometry(&groups, &[0, 0], None, ColorSpace::Linear, false);
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[],
+        });
+        let bind_group =
+            std::sync::Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &layout,
+                entries: &[],
+            }));
+        let prepared_groups = geometry
+            .instance_ranges
+            .iter()
+            .map(|range| PreparedGroup {
+                texture_bind_group: bind_group.clone(),
+                bindless: false,
+                lighting_bind_group: None,
+                material: None,
+                alpha_mode: AlphaMode::Straight,
+                effects_page: 0,
+                instance_start: range.start,
+                instance_end: range.end,
+            })
+            .collect();
+
+        let split = split_effects_pages(prepared_groups, &mut geometry.instances, 3);
+        assert_eq!(
+            split
+                .iter()
+                .map(|group| (group.effects_page, group.instance_start..group.instance_end))
+                .collect::<Vec<_>>(),
+            [(0, 0..4), (0, 4..7), (1, 7..8)]
+        );
+        assert_eq!(
+            geometry
+                .instances
+                .iter()
+                .map(|instance| instance.effects_index)
+                .collect::<Vec<_>>(),
+            [NO_EFFECTS, 0, NO_EFFECTS, 1, NO_EFFECTS, 2, NO_EFFECTS, 0]
+        );
+    }
+
+    #[test]
+    fn effects_texture_shrinks() {
+        let Some((device, queue)) = test_device() else {
+            return;
+        };
+
+        let renderer = Renderer::with_options(
+            &device,
+            wgpu::TextureFormat::Rgba8Unorm,
+            RendererOptions {
+                buffer_capacity: BufferCapacity {
+                    shrink_after: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        );
+        let texture = create_dummy_texture(&device, wgpu::TextureFormat::Rgba8Unorm, 1);
+        let item = Item {
+            color_offset: LinearColor::new(1.0, 0.0, 0.0, 0.0),
+            ..Default::default()
+        };
+        let size = wgpu::Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let mut batch = renderer.create_batch(&device);
+
+        // 256 items' effects fit in a row.
+        renderer.prepare_into(
+            &device,
+            &queue,
+            &mut batch,
+            size,
+            &[Group::new(&texture, vec![item.clone(); 256 * 12])],
+        );
+        assert_eq!(batch.effects_pages.len(), 1);
+        assert_eq!(batch.effects_pages[0].texture.height(), 12);
+
+        renderer.prepare_into(
+            &device,
+            &queue,
+            &mut batch,
+            size,
+            &[Group::new(&texture, vec![item])],
+        );
+        assert_eq!(batch.effects_pages[0].texture.height(), 2);
+    }
 }
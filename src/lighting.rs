@@ -0,0 +1,236 @@
+//! Normal-mapped 2D lighting.
+//!
+//! A [`crate::Group`] with a [`crate::Group::normal_map`] is lit by the lights of the batch it is
+//! prepared into, set with [`crate::Renderer::prepare_lighting`]. Groups without a normal map are
+//! drawn unlit, so lit and unlit groups can be mixed freely in one batch.
+//!
+//! Lighting is computed in [`crate::RendererOptions::tint_color_space`], so it is physically
+//! plausible only on sRGB or float targets.
+
+use encase::UniformBuffer;
+use glam::*;
+
+use crate::uniforms::*;
+
+/// Maximum number of lights per batch. Lights past this are ignored.
+pub const MAX_LIGHTS: usize = 32;
+
+/// The shape of a light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    /// Shines in all directions from [`Light::position`].
+    Point,
+
+    /// Shines in a cone from [`Light::position`].
+    Spot {
+        /// Direction the cone points in, in target space.
+        direction: Vec2,
+
+        /// Angle from the direction, in radians, within which the light is at full strength.
+        inner_angle: f32,
+
+        /// Angle from the direction, in radians, beyond which the light has no effect.
+        outer_angle: f32,
+    },
+
+    /// Shines everywhere from the same direction, like the sun.
+    ///
+    /// [`Light::position`], [`Light::height`], [`Light::radius`] and [`Light::falloff`] are
+    /// ignored.
+    Directional {
+        /// Direction the light travels in, in target space with z pointing towards the viewer.
+        direction: Vec3,
+    },
+}
+
+/// A light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    /// Shape of the light.
+    pub kind: LightKind,
+
+    /// Position, in pixels.
+    ///
+    /// Lights are in logical pixels, like items, and [`Light::height`], [`Light::radius`] and
+    /// [`Light::size`] are scaled with them by [`crate::Renderer::scale_factor`].
+    pub position: Vec2,
+
+    /// Height above the sprites, in pixels. Higher lights light surfaces facing the viewer more
+    /// evenly.
+    pub height: f32,
+
+    /// Color.
+    pub color: crate::LinearColor,
+
+    /// Multiplier for the color.
+    pub intensity: f32,
+
+    /// Distance, in pixels, at which the light has no effect.
+    pub radius: f32,
+
+    /// Exponent of the falloff from the light to its radius. 1 is linear, 2 is quadratic. Must
+    /// be positive.
+    pub falloff: f32,
+
+    /// Radius of the light's source, in pixels. Bigger lights cast softer shadows in a
+    /// [`crate::shadows::LightMap`]: shadow edges are blurred over this distance on either side. 0
+    /// casts hard shadows.
+    pub size: f32,
+}
+
+/// Lights for a batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lighting {
+    /// Light that reaches every surface regardless of its normal.
+    pub ambient: crate::LinearColor,
+
+    /// Lights, up to [`MAX_LIGHTS`].
+    pub lights: Vec<Light>,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            ambient: crate::LinearColor::WHITE,
+            lights: vec![],
+        }
+    }
+}
+
+impl Light {
+    /// Returns the light with its distances scaled by `scale`.
+    pub(crate) fn scaled(&self, scale: f32) -> Self {
+        Self {
+            position: self.position * scale,
+            height: self.height * scale,
+            radius: self.radius * scale,
+            size: self.size * scale,
+            ..*self
+        }
+    }
+}
+
+/// Converts a light color like a tint, so that lights multiply texels in the same color space.
+fn color_to_vec3(
+    color: crate::LinearColor,
+    intensity: f32,
+    color_space: crate::ColorSpace,
+) -> Vec3 {
+    let [r, g, b, _] = crate::tint_to_vec4(color, color_space);
+    vec3(r, g, b) * intensity
+}
+
+pub(crate) fn light_uniforms(light: &Light, color_space: crate::ColorSpace) -> LightUniforms {
+    let (kind, direction, cos_outer, cos_inner) = match light.kind {
+        LightKind::Point => (0, Vec3::ZERO, 0.0, 0.0),
+        LightKind::Spot {
+            direction,
+            inner_angle,
+            outer_angle,
+        } => (
+            1,
+            direction.normalize_or_zero().extend(0.0),
+            outer_angle.cos(),
+            inner_angle.cos(),
+        ),
+        LightKind::Directional { direction } => (2, direction, 0.0, 0.0),
+    };
+    LightUniforms {
+        position: light.position.extend(light.height),
+        kind,
+        color: color_to_vec3(light.color, light.intensity, color_space),
+        radius: light.radius,
+        direction,
+        falloff: light.falloff,
+        cos_outer,
+        cos_inner,
+        size: light.size,
+    }
+}
+
+pub(crate) fn lighting_uniforms(
+    lighting: &Lighting,
+    color_space: crate::ColorSpace,
+) -> LightingUniforms {
+    let mut uniforms = LightingUniforms {
+        ambient: color_to_vec3(lighting.ambient, 1.0, color_space),
+        light_count: lighting.lights.len().min(MAX_LIGHTS) as u32,
+        lights: [LightUniforms::default(); MAX_LIGHTS],
+    };
+    for (uniform, light) in uniforms.lights.iter_mut().zip(lighting.lights.iter()) {
+        *uniform = light_uniforms(light, color_space);
+    }
+    uniforms
+}
+
+pub(crate) fn create_lighting_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("spright: lighting_bind_group_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+        ],
+    })
+}
+
+impl crate::Renderer {
+    /// Sets the lights for normal-mapped groups in `batch`.
+    ///
+    /// Light and ambient colors are converted to [`crate::RendererOptions::tint_color_space`] like
+    /// tints.
+    ///
+    /// Batches start with white ambient light and no lights, which draws lit groups as if they
+    /// were unlit.
+    pub fn prepare_lighting(
+        &self,
+        queue: &wgpu::Queue,
+        batch: &crate::PreparedBatch,
+        lighting: &Lighting,
+    ) {
+        queue.write_buffer(&batch.lighting_uniforms_buffer, 0, &{
+            let mut buffer = UniformBuffer::new(vec![]);
+            buffer
+                .write(&lighting_uniforms(
+                    &Lighting {
+                        ambient: lighting.ambient,
+                        lights: lighting
+                            .lights
+                            .iter()
+                            .map(|light| light.scaled(self.scale_factor()))
+                            .collect(),
+                    },
+                    self.tint_color_space(),
+                ))
+                .unwrap();
+            buffer.into_inner()
+        });
+    }
+}
@@ -0,0 +1,86 @@
+struct Light {
+    // xy in target pixels, z is the height above the sprites.
+    position: vec3<f32>,
+    // 0: point, 1: spot, 2: directional.
+    kind: u32,
+    // Color multiplied by intensity.
+    color: vec3<f32>,
+    radius: f32,
+    direction: vec3<f32>,
+    falloff: f32,
+    cos_outer: f32,
+    cos_inner: f32,
+    // Radius of the light's source, only used by shadows.
+    size: f32,
+}
+
+struct LightingUniforms {
+    ambient: vec3<f32>,
+    light_count: u32,
+    lights: array<Light, 32>,
+}
+
+@group(2) @binding(0)
+var<uniform> lighting: LightingUniforms;
+// Normal maps have the same layout as the texture, so are bound the same way.
+@group(2) @binding(1)
+var normal_map: texture_2d_array<f32>;
+@group(2) @binding(2)
+var normal_map_2d: texture_2d<f32>;
+
+// Samples the normal map and rotates the normal from texture space into target space.
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+    let coords = in.tex_coords / texture_uniforms.size.xy;
+    var sample: vec4<f32>;
+    if (texture_uniforms.layered & 1) == 0 {
+        sample = textureSampleBias(normal_map_2d, s, coords, texture_uniforms.lod_bias);
+    } else {
+        sample = textureSampleBias(normal_map, s, coords, in.layer, texture_uniforms.lod_bias);
+    }
+    var n = sample.xyz * 2.0 - 1.0;
+    // Normal maps point green up, but texture rows go down.
+    n.y = -n.y;
+
+    // Target-to-texture Jacobian. Normals transform by the inverse transpose of the
+    // texture-to-target transform, which is its transpose.
+    let jacobian = mat2x2<f32>(dpdx(in.tex_coords), dpdy(in.tex_coords));
+    let xy = transpose(jacobian) * n.xy;
+    if dot(xy, xy) > 0.0 {
+        n = vec3<f32>(normalize(xy) * length(n.xy), n.z);
+    }
+    return normalize(n);
+}
+
+fn light_contribution(light: Light, position: vec2<f32>, n: vec3<f32>) -> vec3<f32> {
+    var l: vec3<f32>;
+    var attenuation = 1.0;
+    if light.kind == 2u {
+        l = normalize(-light.direction);
+    } else {
+        let to_light = light.position - vec3<f32>(position, 0.0);
+        let dist = length(to_light.xy);
+        attenuation = pow(saturate(1.0 - dist / max(light.radius, 1e-6)), light.falloff);
+        l = normalize(to_light);
+        if light.kind == 1u {
+            let from_light = select(vec2<f32>(0.0), -to_light.xy / dist, dist > 0.0);
+            attenuation *= smoothstep(
+                light.cos_outer,
+                light.cos_inner,
+                dot(from_light, light.direction.xy),
+            );
+        }
+    }
+    return light.color * max(dot(n, l), 0.0) * attenuation;
+}
+
+@fragment
+fn fs_lit(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = surface_normal(in);
+    let color = sample_texture(in) * vertex_tint(in);
+
+    var light = lighting.ambient;
+    for (var i = 0u; i < min(lighting.light_count, 32u); i++) {
+        light += light_contribution(lighting.lights[i], in.position.xy, n);
+    }
+    return apply_effects(in, color_transform(in, vec4<f32>(color.rgb * light, color.a)));
+}
@@ -0,0 +1,361 @@
+//! Custom fragment shaders for groups.
+//!
+//! A material's WGSL source must define a fragment function with the signature:
+//!
+//! ```wgsl
+//! fn material(in: MaterialInput) -> vec4<f32>
+//! ```
+//!
+//! where `MaterialInput` is:
+//!
+//! ```wgsl
+//! struct MaterialInput {
+//!     // Fragment position, in target pixels.
+//!     position: vec4<f32>,
+//!     // Texture coordinates, in texels.
+//!     tex_coords: vec2<f32>,
+//!     layer: u32,
+//!     tint: vec4<f32>,
+//!     // Untinted texture sample.
+//!     sample: vec4<f32>,
+//! }
+//! ```
+//!
+//! The item's color matrix and offset are applied to the returned color.
+//!
+//! With [`crate::AlphaMode::Premultiplied`], `tint` and `sample` are both premultiplied, and the
+//! result is blended as premultiplied.
+//!
+//! The source may declare the following bindings in `@group(2)`:
+//!
+//! - `@binding(0)`: a `var<uniform>` block, if [`MaterialDescriptor::uniforms`] is provided.
+//! - `@binding(1)` onwards: a `texture_2d<f32>` for each of [`MaterialDescriptor::textures`].
+//!
+//! Declaring any other binding, or one of these with a different type, is a
+//! [`crate::ShaderError`], and so is a texture with more than one layer.
+//!
+//! The group's texture (`t` if it has more than one layer, `t_2d` otherwise), sampler (`s`) and
+//! texture uniforms (`texture_uniforms`) are also in scope.
+
+use std::sync::Arc;
+
+use wgpu::util::DeviceExt as _;
+
+/// Describes a [`Material`].
+#[derive(Debug, Clone)]
+pub struct MaterialDescriptor<'a> {
+    /// Debug label.
+    pub label: Option<&'a str>,
+
+    /// WGSL source defining the `material` function.
+    pub source: &'a str,
+
+    /// Initial contents of the uniform block, if any.
+    pub uniforms: Option<&'a [u8]>,
+
+    /// Extra textures available to the shader. Each must have a single layer.
+    pub textures: &'a [&'a wgpu::Texture],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MaterialPipelineKey {
+    source: String,
+    has_uniforms: bool,
+    num_textures: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct MaterialPipeline {
+    pub render_pipelines: crate::SpritePipelines,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+/// A custom fragment shader, with its uniforms and extra textures.
+///
+/// Materials are cheap to clone. Materials created from the same source share a pipeline.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub(crate) pipeline: Arc<MaterialPipeline>,
+    pub(crate) bind_group: Arc<wgpu::BindGroup>,
+    uniforms_buffer: Option<Arc<wgpu::Buffer>>,
+}
+
+impl Material {
+    /// Overwrites the contents of the uniform block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the material was created without uniforms.
+    pub fn write_uniforms(&self, queue: &wgpu::Queue, data: &[u8]) {
+        queue.write_buffer(
+            self.uniforms_buffer
+                .as_ref()
+                .expect("material has no uniforms"),
+            0,
+            data,
+        );
+    }
+}
+
+/// Material bindings live after the texture and target uniforms bind groups.
+const MATERIAL_GROUP: u32 = 2;
+
+impl crate::Renderer {
+    /// Creates a material.
+    ///
+    /// Pipelines are cached, so creating materials that only differ in uniforms or textures is
+    /// cheap.
+    pub fn create_material(
+        &mut self,
+        device: &wgpu::Device,
+        desc: &MaterialDescriptor,
+    ) -> Result<Material, crate::ShaderError> {
+        if let Some(index) = desc
+            .textures
+            .iter()
+            .position(|texture| texture.depth_or_array_layers() > 1)
+        {
+            return Err(crate::ShaderError::LayeredTexture { index });
+        }
+
+        let key = MaterialPipelineKey {
+            source: desc.source.to_string(),
+            has_uniforms: desc.uniforms.is_some(),
+            num_textures: desc.textures.len(),
+        };
+
+        let pipeline = if let Some(pipeline) = self.material_pipelines.get(&key) {
+            pipeline.clone()
+        } else {
+            let source = crate::validate_shader(
+                &[
+                    include_str!("sprite_bindings.wgsl"),
+                    include_str!("shader.wgsl"),
+                    include_str!("material.wgsl"),
+                ]
+                .join("\n"),
+                desc.source,
+                |group, binding| match (group, binding) {
+                    (MATERIAL_GROUP, 0) if desc.uniforms.is_some() => {
+                        Some(crate::ProvidedBinding::Uniform)
+                    }
+                    (MATERIAL_GROUP, 1..) if binding as usize <= desc.textures.len() => {
+                        Some(crate::ProvidedBinding::Texture2d)
+                    }
+                    _ => None,
+                },
+            )?;
+
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("spright: material_bind_group_layout"),
+                    entries: &desc
+                        .uniforms
+                        .iter()
+                        .map(|_| wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        })
+                        .chain(
+                            (0..desc.textures.len()).map(|i| wgpu::BindGroupLayoutEntry {
+                                binding: i as u32 + 1,
+                                visibility: wgpu::ShaderStages::FRAGMENT,
+                                ty: wgpu::BindingType::Texture {
+                                    multisampled: false,
+                                    view_dimension: wgpu::TextureViewDimension::D2,
+                                    sample_type: wgpu::TextureSampleType::Float {
+                                        filterable: true,
+                                    },
+                                },
+                                count: None,
+                            }),
+                        )
+                        .collect::<Vec<_>>(),
+                });
+
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: desc.label,
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+
+            let pipeline = Arc::new(MaterialPipeline {
+                render_pipelines: crate::SpritePipelines::new(
+                    device,
+                    "spright: material_pipeline",
+                    self.texture_format,
+                    &self.options,
+                    &[
+                        &self.texture_bind_group_layout,
+                        &self.target_uniforms_bind_group_layout,
+                        &bind_group_layout,
+                    ],
+                    &shader,
+                    "fs_material",
+                ),
+                bind_group_layout,
+            });
+            self.material_pipelines.insert(key, pipeline.clone());
+            pipeline
+        };
+
+        let uniforms_buffer = desc.uniforms.map(|uniforms| {
+            Arc::new(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("spright: material_uniforms_buffer"),
+                    contents: uniforms,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                }),
+            )
+        });
+
+        let views = desc
+            .textures
+            .iter()
+            .map(|texture| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: material_bind_group"),
+            layout: &pipeline.bind_group_layout,
+            entries: &uniforms_buffer
+                .iter()
+                .map(|buffer| wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                })
+                .chain(
+                    views
+                        .iter()
+                        .enumerate()
+                        .map(|(i, view)| wgpu::BindGroupEntry {
+                            binding: i as u32 + 1,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        }),
+                )
+                .collect::<Vec<_>>(),
+        });
+
+        Ok(Material {
+            pipeline,
+            bind_group: Arc::new(bind_group),
+            uniforms_buffer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_are_checked_against_the_layout() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let mut renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let mut create = |source: &str| {
+            renderer
+                .create_material(
+                    &device,
+                    &MaterialDescriptor {
+                        label: None,
+                        source,
+                        uniforms: Some(&[0; 16]),
+                        textures: &[],
+                    },
+                )
+                .map(|_| ())
+        };
+
+        assert!(create(
+            "@group(2) @binding(0) var<uniform> u: vec4<f32>;
+            fn material(in: MaterialInput) -> vec4<f32> { return in.sample * u; }"
+        )
+        .is_ok());
+
+        // A texture where the uniforms are.
+        assert!(matches!(
+            create(
+                "@group(2) @binding(0) var u: texture_2d<f32>;
+                fn material(in: MaterialInput) -> vec4<f32> {
+                    return textureLoad(u, vec2(0), 0);
+                }"
+            ),
+            Err(crate::ShaderError::MismatchedBinding {
+                group: 2,
+                binding: 0
+            })
+        ));
+
+        // A binding in the library's own groups.
+        assert!(matches!(
+            create(
+                "@group(0) @binding(8) var<uniform> u: vec4<f32>;
+                fn material(in: MaterialInput) -> vec4<f32> { return in.sample * u; }"
+            ),
+            Err(crate::ShaderError::InvalidBinding {
+                group: 0,
+                binding: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn layered_textures_are_rejected() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let mut renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = |layers| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: layers,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+        let (single, layered) = (texture(1), texture(2));
+        let mut create = |textures: &[&wgpu::Texture]| {
+            renderer
+                .create_material(
+                    &device,
+                    &MaterialDescriptor {
+                        label: None,
+                        source: "@group(2) @binding(1) var a: texture_2d<f32>;
+                            @group(2) @binding(2) var b: texture_2d<f32>;
+                            fn material(in: MaterialInput) -> vec4<f32> {
+                                return textureLoad(a, vec2(0), 0) * textureLoad(b, vec2(0), 0);
+                            }",
+                        uniforms: None,
+                        textures,
+                    },
+                )
+                .map(|_| ())
+        };
+
+        assert!(create(&[&single, &single]).is_ok());
+        assert!(matches!(
+            create(&[&single, &layered]),
+            Err(crate::ShaderError::LayeredTexture { index: 1 })
+        ));
+    }
+}
@@ -0,0 +1,18 @@
+struct MaterialInput {
+    // Fragment position, in target pixels.
+    position: vec4<f32>,
+    // Texture coordinates, in texels.
+    tex_coords: vec2<f32>,
+    layer: u32,
+    tint: vec4<f32>,
+    // Untinted texture sample.
+    sample: vec4<f32>,
+}
+
+@fragment
+fn fs_material(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = material(
+        MaterialInput(in.position, in.tex_coords, in.layer, vertex_tint(in), sample_texture(in)),
+    );
+    return apply_effects(in, color_transform(in, color));
+}
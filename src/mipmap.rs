@@ -0,0 +1,306 @@
+//! Mip chain generation.
+//!
+//! Sprites drawn much smaller than their source shimmer unless the texture has mip levels and the
+//! renderer samples them, e.g. with [`crate::Filtering::Mipmapped`]. [`MipmapGenerator`] fills in
+//! every mip level of every layer of a texture from its first level on the GPU.
+
+use std::collections::HashMap;
+
+use encase::UniformBuffer;
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+use crate::uniforms::*;
+
+/// Returns the number of mip levels in a full mip chain for a texture of the given size.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Generates mip levels by repeatedly downsampling with a box filter.
+///
+/// Odd sized levels are filtered with three taps along that axis, so their last row and column
+/// still contribute to the next level.
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    /// Creates a new mip chain generator.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("mipmap.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: mipmap_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("spright: mipmap_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Records commands that fill mip levels 1 and up of every layer of `texture` from level 0.
+    ///
+    /// `texture` must have been created with [`wgpu::TextureUsages::TEXTURE_BINDING`] and
+    /// [`wgpu::TextureUsages::RENDER_ATTACHMENT`], in a color format that can be rendered to.
+    ///
+    /// If the texture is an atlas whose regions are laid out on a grid of `tile_size` texels,
+    /// pass it as `tile_size` and each level is downsampled within its tile only, so regions
+    /// don't bleed into each other. Tile sizes should be powers of two so the grid stays aligned
+    /// at every level. Levels smaller than a tile mix whole tiles together, so the mip chain is
+    /// only useful down to the level where a tile is a single texel.
+    ///
+    /// `alpha_mode` is how the texture's colors relate to its alpha. Straight alpha colors are
+    /// premultiplied while averaging, so transparent texels don't darken the edges of opaque ones.
+    pub fn generate(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        tile_size: Option<UVec2>,
+        alpha_mode: crate::AlphaMode,
+    ) {
+        let format = texture.format();
+        let pipeline = self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("spright: mipmap_pipeline"),
+                cache: None,
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: Some("fs_downsample"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::all(),
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        });
+
+        for level in 1..texture.mip_level_count() {
+            // Tile size at the source level.
+            let tile_size = tile_size.map_or(UVec2::ZERO, |tile_size| {
+                (tile_size >> (level - 1)).max(UVec2::ONE)
+            });
+
+            let mut uniforms = UniformBuffer::new(vec![]);
+            uniforms
+                .write(&MipmapUniforms {
+                    tile_size,
+                    premultiplied: (alpha_mode == crate::AlphaMode::Premultiplied) as u32,
+                })
+                .unwrap();
+            let uniforms_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("spright: mipmap_uniforms_buffer"),
+                contents: &uniforms.into_inner(),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            for layer in 0..texture.depth_or_array_layers() {
+                let view = |level| {
+                    texture.create_view(&wgpu::TextureViewDescriptor {
+                        label: Some("spright: mipmap_view"),
+                        dimension: Some(wgpu::TextureViewDimension::D2),
+                        base_mip_level: level,
+                        mip_level_count: Some(1),
+                        base_array_layer: layer,
+                        array_layer_count: Some(1),
+                        ..Default::default()
+                    })
+                };
+                let source_view = view(level - 1);
+                let target_view = view(level);
+
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("spright: mipmap_bind_group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: uniforms_buffer.as_entire_binding(),
+                        },
+                    ],
+                });
+
+                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("spright: mipmap_pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target_view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    ..Default::default()
+                });
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(0, &bind_group, &[]);
+                rpass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Uploads `texels` as the first level of a square texture, generates its mip chain and reads
+    /// back the first texel of the second level.
+    fn downsample(texels: &[[u8; 4]], alpha_mode: crate::AlphaMode) -> Option<[u8; 4]> {
+        let (device, queue) = crate::test_device()?;
+
+        let size = texels.len().isqrt() as u32;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_level_count(size, size),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels.as_flattened(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size * 4),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 256,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        MipmapGenerator::new(&device).generate(&device, &mut encoder, &texture, None, alpha_mode);
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                mip_level: 1,
+                ..texture.as_image_copy()
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(256),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit([encoder.finish()]);
+
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = buffer.slice(..).get_mapped_range();
+        Some(data[..4].try_into().unwrap())
+    }
+
+    fn assert_near(actual: [u8; 4], expected: [u8; 4]) {
+        assert!(
+            actual.iter().zip(expected).all(|(a, e)| a.abs_diff(e) <= 1),
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn odd_levels_filter_every_texel() {
+        // Only the last row and column are lit, which a 2x2 box filter would drop entirely.
+        let mut texels = [[0, 0, 0, 0xff]; 9];
+        for i in [2, 5, 6, 7, 8] {
+            texels[i] = [0xff; 4];
+        }
+        let Some(texel) = downsample(&texels, crate::AlphaMode::Straight) else {
+            return;
+        };
+        // 5 of 9 texels, equally weighted.
+        assert_near(texel, [142, 142, 142, 0xff]);
+    }
+
+    #[test]
+    fn transparent_texels_do_not_darken_edges() {
+        let red = [0xff, 0, 0, 0xff];
+        let clear = [0; 4];
+        let Some(texel) = downsample(&[red, clear, red, clear], crate::AlphaMode::Straight) else {
+            return;
+        };
+        assert_near(texel, [0xff, 0, 0, 128]);
+
+        // Premultiplied colors are already weighted, so they are averaged as they are.
+        let Some(texel) = downsample(&[red, clear, red, clear], crate::AlphaMode::Premultiplied)
+        else {
+            return;
+        };
+        assert_near(texel, [128, 0, 0, 128]);
+    }
+}
@@ -0,0 +1,74 @@
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+struct MipmapUniforms {
+    tile_size: vec2<u32>,
+    premultiplied: u32,
+}
+
+@group(0) @binding(1)
+var<uniform> mipmap_uniforms: MipmapUniforms;
+
+// Covers the target with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// Weights of the source texels 2x, 2x + 1 and 2x + 2 for target texel x along an axis with
+// `source_size` texels. Even sizes average pairs. Odd sizes have one texel more than twice the
+// target size, so each target texel spreads over three source texels, weighted by how much of
+// each its footprint covers.
+fn tap_weights(x: u32, source_size: u32) -> vec3<f32> {
+    if (source_size & 1u) == 0u {
+        return vec3<f32>(0.5, 0.5, 0.0);
+    }
+    let half_size = f32(source_size / 2u);
+    return vec3<f32>(half_size - f32(x), half_size, f32(x) + 1.0) / f32(source_size);
+}
+
+// Filters the block of source texels under the fragment, never reading outside the tile that
+// contains the block so neighboring tiles don't bleed into each other. Straight alpha colors are
+// weighted by their alpha, so transparent texels don't darken their neighbors.
+@fragment
+fn fs_downsample(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let source_size = textureDimensions(source);
+    let target_coords = vec2<u32>(position.xy);
+    let origin = target_coords * 2u;
+    let weights_x = tap_weights(target_coords.x, source_size.x);
+    let weights_y = tap_weights(target_coords.y, source_size.y);
+
+    var lo = vec2<u32>(0u);
+    var hi = source_size - 1u;
+    let tile_size = max(mipmap_uniforms.tile_size, vec2<u32>(1u));
+    if all(mipmap_uniforms.tile_size > vec2<u32>(0u)) {
+        lo = origin / tile_size * tile_size;
+        hi = min(hi, lo + tile_size - 1u);
+    }
+
+    var sum = vec4<f32>(0.0);
+    var straight_sum = vec3<f32>(0.0);
+    for (var y = 0u; y < 3u; y++) {
+        for (var x = 0u; x < 3u; x++) {
+            let coords = clamp(origin + vec2<u32>(x, y), lo, hi);
+            let texel = textureLoad(source, coords, 0);
+            let weight = weights_x[x] * weights_y[y];
+            if mipmap_uniforms.premultiplied != 0u {
+                sum += texel * weight;
+            } else {
+                sum += vec4<f32>(texel.rgb * texel.a, texel.a) * weight;
+                straight_sum += texel.rgb * weight;
+            }
+        }
+    }
+
+    if mipmap_uniforms.premultiplied != 0u {
+        return sum;
+    }
+    // Fully transparent blocks have no alpha to divide by, so keep their plain average instead.
+    if sum.a <= 0.0 {
+        return vec4<f32>(straight_sum, 0.0);
+    }
+    return vec4<f32>(sum.rgb / sum.a, sum.a);
+}
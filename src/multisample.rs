@@ -0,0 +1,120 @@
+//! Multisample anti-aliasing.
+
+/// Owns the multisampled color attachment, and depth/stencil attachment if any, for a
+/// [`crate::Renderer`] created with [`crate::RendererOptions::sample_count`] above 1.
+///
+/// Render passes begun with [`MultisampleTarget::begin_render_pass`] resolve into the caller's
+/// target. If the sample count is 1, they draw to the caller's target directly.
+pub struct MultisampleTarget {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    depth_stencil_format: Option<wgpu::TextureFormat>,
+    size: wgpu::Extent3d,
+    color_view: Option<wgpu::TextureView>,
+    depth_stencil_view: Option<wgpu::TextureView>,
+}
+
+impl MultisampleTarget {
+    /// Creates attachments matching the format, sample count and depth/stencil format of
+    /// `renderer`.
+    pub fn new(device: &wgpu::Device, renderer: &crate::Renderer, size: wgpu::Extent3d) -> Self {
+        let options = renderer.options();
+        let mut target = Self {
+            format: renderer.texture_format(),
+            sample_count: options.sample_count,
+            depth_stencil_format: options.depth_stencil.as_ref().map(|state| state.format),
+            size: wgpu::Extent3d::default(),
+            color_view: None,
+            depth_stencil_view: None,
+        };
+        target.resize(device, size);
+        target
+    }
+
+    /// Returns the size of the attachments.
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.size
+    }
+
+    /// Reallocates the attachments if `size` differs from their current size.
+    pub fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        let size = wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        if size == self.size {
+            return;
+        }
+        self.size = size;
+
+        let create_view = |label, format, sample_count| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size,
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        self.color_view = (self.sample_count > 1)
+            .then(|| create_view("spright: multisample_color", self.format, self.sample_count));
+        self.depth_stencil_view = self.depth_stencil_format.map(|format| {
+            create_view(
+                "spright: multisample_depth_stencil",
+                format,
+                self.sample_count,
+            )
+        });
+    }
+
+    /// Begins a render pass that resolves into `target`.
+    ///
+    /// `target` must be the same size as the attachments. If `clear` is `None`, the previous
+    /// contents of the multisampled attachment are kept. The depth/stencil attachment, if any, is
+    /// always cleared.
+    pub fn begin_render_pass<'encoder>(
+        &self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        clear: Option<wgpu::Color>,
+    ) -> wgpu::RenderPass<'encoder> {
+        let (view, resolve_target) = match &self.color_view {
+            Some(color_view) => (color_view, Some(target)),
+            None => (target, None),
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("spright: multisample_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: self.depth_stencil_view.as_ref().map(|view| {
+                let format = self.depth_stencil_format.unwrap();
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: format.has_depth_aspect().then_some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: format.has_stencil_aspect().then_some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }
+            }),
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,28 @@
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    age: f32,
+    lifetime: f32,
+    rotation: f32,
+    angular_velocity: f32,
+}
+
+struct ParticleUniforms {
+    position: vec2<f32>,
+    spread: vec2<f32>,
+    velocity: vec2<f32>,
+    velocity_spread: vec2<f32>,
+    acceleration: vec2<f32>,
+    lifetime: vec2<f32>,
+    rotation: vec2<f32>,
+    angular_velocity: vec2<f32>,
+    scale: vec2<f32>,
+    start_tint: vec4<f32>,
+    end_tint: vec4<f32>,
+    src_offset: vec2<f32>,
+    src_size: vec2<f32>,
+    src_layer: u32,
+    emit_count: u32,
+    seed: u32,
+    dt: f32,
+}
@@ -0,0 +1,696 @@
+//! GPU-driven particle simulation.
+//!
+//! Particle state lives entirely in a GPU storage buffer: a compute pass advances and emits
+//! particles, and the render pass reads the same buffer from the vertex shader. Particles are
+//! drawn with the same texture bindings, fragment shader and blending as [`crate::Renderer`], so
+//! they composite with regular sprites. The simulation also lists the live particles, and only
+//! those are drawn, with an indirect draw.
+//!
+//! This requires compute shaders and storage buffers in vertex shaders, which are not available on
+//! WebGL2.
+
+use encase::{ShaderSize, UniformBuffer};
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+use crate::uniforms::*;
+
+/// Describes how particles are emitted and how they evolve over their lifetime.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    /// Position particles are emitted from, in logical pixels like [`crate::Item::transform`].
+    pub position: Vec2,
+
+    /// Maximum random offset from the emitter position, per axis.
+    pub spread: Vec2,
+
+    /// Initial velocity, in logical pixels per second.
+    pub velocity: Vec2,
+
+    /// Maximum random deviation from the initial velocity, per axis.
+    pub velocity_spread: Vec2,
+
+    /// Constant acceleration, e.g. gravity, in logical pixels per second squared.
+    pub acceleration: Vec2,
+
+    /// Range of lifetimes, in seconds.
+    pub lifetime: std::ops::Range<f32>,
+
+    /// Range of initial rotations, in radians.
+    pub rotation: std::ops::Range<f32>,
+
+    /// Range of angular velocities, in radians per second.
+    pub angular_velocity: std::ops::Range<f32>,
+
+    /// Scale at the start of a particle's lifetime.
+    pub start_scale: f32,
+
+    /// Scale at the end of a particle's lifetime.
+    pub end_scale: f32,
+
+    /// Tint at the start of a particle's lifetime. Like [`crate::Item::tint`], components may
+    /// exceed 1 on float targets.
+    pub start_tint: crate::LinearColor,
+
+    /// Tint at the end of a particle's lifetime.
+    pub end_tint: crate::LinearColor,
+
+    /// Source offset from the texture.
+    pub src_offset: IVec2,
+
+    /// Source size.
+    pub src_size: UVec2,
+
+    /// Source layer.
+    pub src_layer: u32,
+
+    /// Number of particles emitted per second.
+    pub rate: f32,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            spread: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            velocity_spread: Vec2::ZERO,
+            acceleration: Vec2::ZERO,
+            lifetime: 1.0..1.0,
+            rotation: 0.0..0.0,
+            angular_velocity: 0.0..0.0,
+            start_scale: 1.0,
+            end_scale: 1.0,
+            start_tint: crate::LinearColor::WHITE,
+            end_tint: crate::LinearColor::new(1.0, 1.0, 1.0, 0.0),
+            src_offset: IVec2::ZERO,
+            src_size: UVec2::ZERO,
+            src_layer: 0,
+            rate: 0.0,
+        }
+    }
+}
+
+/// A fixed-capacity pool of particles simulated on the GPU.
+pub struct ParticleSystem {
+    capacity: u32,
+    workgroups: (u32, u32),
+    texture_format: wgpu::TextureFormat,
+    tint_color_space: crate::ColorSpace,
+    particle_uniforms_buffer: wgpu::Buffer,
+    emitted_buffer: wgpu::Buffer,
+    draw_args_buffer: wgpu::Buffer,
+    simulate_pipeline: wgpu::ComputePipeline,
+    simulate_bind_group: wgpu::BindGroup,
+    render_pipelines: crate::SpritePipelines,
+    particles_bind_group: wgpu::BindGroup,
+    texture_uniforms_buffer: wgpu::Buffer,
+    target_uniforms_buffer: wgpu::Buffer,
+    target_uniforms_bind_group: wgpu::BindGroup,
+    /// The texture last prepared with, and its bind group.
+    texture_bind_group: Option<(wgpu::Texture, wgpu::BindGroup)>,
+    alpha_mode: crate::AlphaMode,
+    pending_emit_count: f32,
+    seed: u32,
+}
+
+impl ParticleSystem {
+    const WORKGROUP_SIZE: u32 = 64;
+
+    /// Creates a new particle system that can hold up to `capacity` live particles.
+    ///
+    /// The particle system renders to the same texture format as `renderer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the particles don't fit in a storage buffer binding of the device's
+    /// [`wgpu::Limits::max_storage_buffer_binding_size`].
+    pub fn new(device: &wgpu::Device, renderer: &crate::Renderer, capacity: u32) -> Self {
+        let limits = device.limits();
+        let particles_size = u64::from(Particle::SHADER_SIZE) * capacity.max(1) as u64;
+        assert!(
+            particles_size <= limits.max_storage_buffer_binding_size as u64
+                && particles_size <= limits.max_buffer_size,
+            "{capacity} particles don't fit in a storage buffer binding on this device",
+        );
+
+        // Workgroups are spread over two dimensions, as one can't hold more than
+        // `max_compute_workgroups_per_dimension`.
+        let workgroup_count = capacity.div_ceil(Self::WORKGROUP_SIZE).max(1);
+        let workgroups_x = workgroup_count.min(limits.max_compute_workgroups_per_dimension);
+        let workgroups = (workgroups_x, workgroup_count.div_ceil(workgroups_x));
+
+        let simulate_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("spright: particles_simulate"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("particle.wgsl"),
+                    include_str!("particles_simulate.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let render_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("spright: particles_render"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("sprite_bindings.wgsl"),
+                    include_str!("shader.wgsl"),
+                    include_str!("particle.wgsl"),
+                    include_str!("particles_render.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let particles_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: particles_buffer"),
+            size: particles_size,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let emitted_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: emitted_buffer"),
+            size: std::mem::size_of::<u32>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Written by the simulation: the instance count is the number of live particles, and the
+        // alive buffer holds their indices.
+        let draw_args_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("spright: draw_args_buffer"),
+            contents: wgpu::util::DrawIndirectArgs {
+                vertex_count: 4,
+                instance_count: 0,
+                first_vertex: 0,
+                first_instance: 0,
+            }
+            .as_bytes(),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let alive_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: alive_buffer"),
+            size: std::mem::size_of::<u32>() as u64 * capacity.max(1) as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let particle_uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: particle_uniforms_buffer"),
+            size: ParticleUniforms::SHADER_SIZE.into(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let simulate_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("spright: simulate_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let particles_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("spright: particles_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let simulate_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: simulate_bind_group"),
+            layout: &simulate_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particles_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: emitted_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: particle_uniforms_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: draw_args_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: alive_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let particles_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: particles_bind_group"),
+            layout: &particles_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: particles_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particle_uniforms_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: alive_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let texture_uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: particles.texture_uniforms_buffer"),
+            size: TextureUniforms::SHADER_SIZE.into(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let target_uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: particles.target_uniforms_buffer"),
+            size: TargetUniforms::SHADER_SIZE.into(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Particles have no effects, but share the sprite shader's bindings.
+        let target_uniforms_bind_group = renderer.create_target_uniforms_bind_group(
+            device,
+            &target_uniforms_buffer,
+            &crate::create_effects_texture(device, 1),
+        );
+
+        Self {
+            capacity,
+            workgroups,
+            texture_format: renderer.texture_format,
+            tint_color_space: renderer.tint_color_space(),
+            simulate_pipeline: device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("spright: simulate_pipeline"),
+                layout: Some(
+                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some("spright: simulate_pipeline.layout"),
+                        bind_group_layouts: &[&simulate_bind_group_layout],
+                        push_constant_ranges: &[],
+                    }),
+                ),
+                module: &simulate_shader,
+                entry_point: Some("cs_simulate"),
+                compilation_options: Default::default(),
+                cache: None,
+            }),
+            render_pipelines: crate::SpritePipelines::with_vertex(
+                device,
+                "spright: particles_render_pipeline",
+                renderer.texture_format,
+                &renderer.options,
+                &[
+                    &renderer.texture_bind_group_layout,
+                    &renderer.target_uniforms_bind_group_layout,
+                    &particles_bind_group_layout,
+                ],
+                wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: Some("vs_particle"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                "fs_main",
+            ),
+            particle_uniforms_buffer,
+            emitted_buffer,
+            draw_args_buffer,
+            simulate_bind_group,
+            particles_bind_group,
+            texture_uniforms_buffer,
+            target_uniforms_buffer,
+            target_uniforms_bind_group,
+            texture_bind_group: None,
+            alpha_mode: renderer.options.alpha_mode,
+            pending_emit_count: 0.0,
+            seed: 0,
+        }
+    }
+
+    /// Returns the maximum number of live particles.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Queues `count` particles to be emitted on the next call to [`ParticleSystem::simulate`], in
+    /// addition to those emitted by [`Emitter::rate`].
+    pub fn burst(&mut self, count: u32) {
+        self.pending_emit_count += count as f32;
+    }
+
+    /// Encodes a simulation step of `dt` seconds.
+    ///
+    /// Uniforms are uploaded via `queue`, so this should be called at most once per submission.
+    pub fn simulate(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        emitter: &Emitter,
+        dt: f32,
+    ) {
+        self.pending_emit_count += emitter.rate * dt;
+        let emit_count = self.pending_emit_count.floor();
+        self.pending_emit_count -= emit_count;
+        self.seed = self.seed.wrapping_add(1);
+
+        queue.write_buffer(&self.particle_uniforms_buffer, 0, &{
+            let mut buffer = UniformBuffer::new(vec![]);
+            buffer
+                .write(&ParticleUniforms {
+                    position: emitter.position,
+                    spread: emitter.spread,
+                    velocity: emitter.velocity,
+                    velocity_spread: emitter.velocity_spread,
+                    acceleration: emitter.acceleration,
+                    lifetime: vec2(emitter.lifetime.start, emitter.lifetime.end),
+                    rotation: vec2(emitter.rotation.start, emitter.rotation.end),
+                    angular_velocity: vec2(
+                        emitter.angular_velocity.start,
+                        emitter.angular_velocity.end,
+                    ),
+                    scale: vec2(emitter.start_scale, emitter.end_scale),
+                    start_tint: Vec4::from_array(crate::tint_to_vec4(
+                        emitter.start_tint,
+                        self.tint_color_space,
+                    )),
+                    end_tint: Vec4::from_array(crate::tint_to_vec4(
+                        emitter.end_tint,
+                        self.tint_color_space,
+                    )),
+                    src_offset: emitter.src_offset.as_vec2(),
+                    src_size: emitter.src_size.as_vec2(),
+                    src_layer: emitter.src_layer,
+                    emit_count: emit_count as u32,
+                    seed: self.seed,
+                    dt,
+                })
+                .unwrap();
+            buffer.into_inner()
+        });
+
+        encoder.clear_buffer(&self.emitted_buffer, 0, None);
+        // Reset the instance count; the simulation counts the live particles back up.
+        encoder.clear_buffer(
+            &self.draw_args_buffer,
+            std::mem::size_of::<u32>() as u64,
+            Some(std::mem::size_of::<u32>() as u64),
+        );
+
+        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("spright: simulate_pass"),
+            timestamp_writes: None,
+        });
+        cpass.set_pipeline(&self.simulate_pipeline);
+        cpass.set_bind_group(0, &self.simulate_bind_group, &[]);
+        cpass.dispatch_workgroups(self.workgroups.0, self.workgroups.1, 1);
+    }
+
+    /// Prepares particles for rendering with the given texture.
+    ///
+    /// `alpha_mode` overrides the renderer's [`crate::RendererOptions::alpha_mode`], like
+    /// [`crate::Group::alpha_mode`]. `renderer` must be the renderer this particle system was
+    /// created with.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        renderer: &crate::Renderer,
+        target_size: wgpu::Extent3d,
+        texture: &wgpu::Texture,
+        alpha_mode: Option<crate::AlphaMode>,
+    ) {
+        debug_assert_eq!(renderer.texture_format, self.texture_format);
+        self.alpha_mode = alpha_mode.unwrap_or(renderer.options.alpha_mode);
+
+        // Particles are simulated in logical pixels, so map them to target pixels with the
+        // target's size in logical pixels.
+        queue.write_buffer(&self.target_uniforms_buffer, 0, &{
+            let mut buffer = UniformBuffer::new(vec![]);
+            buffer
+                .write(&TargetUniforms {
+                    size: (crate::target_uniforms(target_size).size / renderer.scale_factor())
+                        .with_z(0.0),
+                })
+                .unwrap();
+            buffer.into_inner()
+        });
+
+        queue.write_buffer(&self.texture_uniforms_buffer, 0, &{
+            let mut buffer = UniformBuffer::new(vec![]);
+            buffer
+                .write(&crate::texture_uniforms(
+                    texture,
+                    None,
+                    0.0,
+                    self.alpha_mode,
+                ))
+                .unwrap();
+            buffer.into_inner()
+        });
+
+        // The uniforms are rewritten in place, so the bind group only changes with the texture.
+        if self
+            .texture_bind_group
+            .as_ref()
+            .is_none_or(|(prepared, _)| prepared != texture)
+        {
+            self.texture_bind_group = Some((
+                texture.clone(),
+                renderer.create_texture_bind_group(
+                    device,
+                    texture,
+                    None,
+                    self.texture_uniforms_buffer.as_entire_buffer_binding(),
+                ),
+            ));
+        }
+    }
+
+    /// Renders prepared particles.
+    pub fn render<'rpass>(&'rpass self, rpass: &mut wgpu::RenderPass<'rpass>) {
+        let Some((_, texture_bind_group)) = &self.texture_bind_group else {
+            return;
+        };
+        rpass.set_pipeline(self.render_pipelines.get(self.alpha_mode));
+        rpass.set_bind_group(0, texture_bind_group, &[]);
+        rpass.set_bind_group(1, &self.target_uniforms_bind_group, &[]);
+        rpass.set_bind_group(2, &self.particles_bind_group, &[]);
+        rpass.draw_indirect(&self.draw_args_buffer, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Copies `size` bytes of `source` to a new buffer and reads them back.
+    fn read_back(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        copy: impl FnOnce(&mut wgpu::CommandEncoder, &wgpu::Buffer),
+        size: u64,
+    ) -> Vec<u8> {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        copy(&mut encoder, &buffer);
+        queue.submit([encoder.finish()]);
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = buffer.slice(..).get_mapped_range().to_vec();
+        data
+    }
+
+    #[test]
+    fn emitted_particles_are_counted_and_drawn() {
+        let Some((device, queue)) = crate::test_device() else {
+            return;
+        };
+
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = crate::Renderer::new(&device, format);
+        let texture = device.create_texture_with_data(
+            &queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 2,
+                    height: 2,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            Default::default(),
+            &[0xff; 2 * 2 * 4],
+        );
+        let target = crate::render_target::RenderTarget::with_format(&device, format, 8, 8);
+        let emitter = Emitter {
+            position: vec2(4.0, 4.0),
+            lifetime: 10.0..10.0,
+            end_tint: crate::LinearColor::WHITE,
+            src_size: uvec2(2, 2),
+            ..Default::default()
+        };
+
+        let mut system = ParticleSystem::new(&device, &renderer, 64);
+        let mut step = |burst, dt| {
+            system.burst(burst);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            system.simulate(&queue, &mut encoder, &emitter, dt);
+            system.prepare(&device, &queue, &renderer, target.size(), &texture, None);
+            {
+                let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::BLACK));
+                system.render(&mut rpass);
+            }
+            let instance_count = read_back(
+                &device,
+                &queue,
+                encoder,
+                |encoder, buffer| {
+                    encoder.copy_buffer_to_buffer(&system.draw_args_buffer, 4, buffer, 0, 4)
+                },
+                4,
+            );
+            let pixels = read_back(
+                &device,
+                &queue,
+                device.create_command_encoder(&Default::default()),
+                |encoder, buffer| {
+                    encoder.copy_texture_to_buffer(
+                        target.texture().as_image_copy(),
+                        wgpu::TexelCopyBufferInfo {
+                            buffer,
+                            layout: wgpu::TexelCopyBufferLayout {
+                                offset: 0,
+                                bytes_per_row: Some(256),
+                                rows_per_image: None,
+                            },
+                        },
+                        target.size(),
+                    )
+                },
+                256 * 8,
+            );
+            let pixel = |x: usize, y: usize| pixels[y * 256 + x * 4..][..4].to_vec();
+            (
+                u32::from_le_bytes(instance_count.try_into().unwrap()),
+                pixel(4, 4),
+                pixel(0, 0),
+            )
+        };
+
+        let (count, center, corner) = step(5, 0.0);
+        assert_eq!(count, 5);
+        assert_eq!(center, [0xff; 4]);
+        assert_eq!(corner, [0, 0, 0, 0xff]);
+
+        // Every particle outlives its lifetime, so none are drawn.
+        let (count, center, _) = step(0, 20.0);
+        assert_eq!(count, 0);
+        assert_eq!(center, [0, 0, 0, 0xff]);
+    }
+}
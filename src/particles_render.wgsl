@@ -0,0 +1,31 @@
+@group(2) @binding(0)
+var<storage, read> particles: array<Particle>;
+@group(2) @binding(1)
+var<uniform> particle_uniforms: ParticleUniforms;
+// Indices of the live particles, one per instance.
+@group(2) @binding(2)
+var<storage, read> alive: array<u32>;
+
+@vertex
+fn vs_particle(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let p = particles[alive[instance_index]];
+
+    let t = p.age / p.lifetime;
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u));
+    let offset = (corner - 0.5) * particle_uniforms.src_size
+        * mix(particle_uniforms.scale.x, particle_uniforms.scale.y, t);
+    let cos_r = cos(p.rotation);
+    let sin_r = sin(p.rotation);
+
+    out.tint = mix(particle_uniforms.start_tint, particle_uniforms.end_tint, t);
+    out.effects_index = NO_EFFECTS;
+    out.tex_coords = particle_uniforms.src_offset + corner * particle_uniforms.src_size;
+    out.layer = particle_uniforms.src_layer;
+    out.position = to_ndc(p.position + vec2<f32>(offset.x * cos_r - offset.y * sin_r, offset.x * sin_r + offset.y * cos_r));
+    return out;
+}
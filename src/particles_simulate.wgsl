@@ -0,0 +1,84 @@
+@group(0) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1)
+var<storage, read_write> emitted: atomic<u32>;
+@group(0) @binding(2)
+var<uniform> particle_uniforms: ParticleUniforms;
+@group(0) @binding(3)
+var<storage, read_write> draw_args: DrawArgs;
+@group(0) @binding(4)
+var<storage, read_write> alive: array<u32>;
+
+// Arguments of the indirect draw, whose instances are the live particles.
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+// PCG hash, see https://www.jcgt.org/published/0009/03/02/.
+fn hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state >> 8u) / 16777216.0;
+}
+
+fn random_range(state: ptr<function, u32>, range: vec2<f32>) -> f32 {
+    return mix(range.x, range.y, random(state));
+}
+
+fn random_spread(state: ptr<function, u32>, spread: vec2<f32>) -> vec2<f32> {
+    let x = random(state);
+    let y = random(state);
+    return (vec2<f32>(x, y) * 2.0 - 1.0) * spread;
+}
+
+fn spawn(index: u32) -> Particle {
+    var state = hash(index ^ hash(particle_uniforms.seed));
+
+    var p: Particle;
+    p.position = particle_uniforms.position + random_spread(&state, particle_uniforms.spread);
+    p.velocity = particle_uniforms.velocity + random_spread(&state, particle_uniforms.velocity_spread);
+    p.age = 0.0;
+    p.lifetime = random_range(&state, particle_uniforms.lifetime);
+    p.rotation = random_range(&state, particle_uniforms.rotation);
+    p.angular_velocity = random_range(&state, particle_uniforms.angular_velocity);
+    return p;
+}
+
+@compute @workgroup_size(64)
+fn cs_simulate(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) num_workgroups: vec3<u32>,
+) {
+    // Large systems are dispatched as rows of workgroups.
+    let index = id.y * num_workgroups.x * 64u + id.x;
+    if index >= arrayLength(&particles) {
+        return;
+    }
+
+    var p = particles[index];
+    if p.age >= p.lifetime {
+        // Dead particles are recycled until this frame's emission budget runs out.
+        if atomicAdd(&emitted, 1u) >= particle_uniforms.emit_count {
+            return;
+        }
+        p = spawn(index);
+    } else {
+        p.velocity += particle_uniforms.acceleration * particle_uniforms.dt;
+        p.position += p.velocity * particle_uniforms.dt;
+        p.rotation += p.angular_velocity * particle_uniforms.dt;
+        p.age += particle_uniforms.dt;
+    }
+    particles[index] = p;
+
+    if p.age < p.lifetime {
+        alive[atomicAdd(&draw_args.instance_count, 1u)] = index;
+    }
+}
@@ -0,0 +1,666 @@
+//! Finding which items are under a point.
+//!
+//! [`Picker`] tests points against item quads on the CPU. For large scenes, [`IdBuffer`] renders
+//! an ID per pixel on the GPU instead.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, OnceLock};
+
+use glam::*;
+
+/// A CPU copy of a texture's alpha channel, for picking by per-pixel alpha.
+#[derive(Debug, Clone)]
+pub struct TextureAlpha {
+    size: UVec3,
+    alpha: Vec<u8>,
+}
+
+impl TextureAlpha {
+    /// Creates a copy from one alpha byte per texel, layer by layer and row by row.
+    pub fn new(width: u32, height: u32, layers: u32, alpha: Vec<u8>) -> Self {
+        assert_eq!(alpha.len(), (width * height * layers) as usize);
+        Self {
+            size: uvec3(width, height, layers),
+            alpha,
+        }
+    }
+
+    /// Creates a copy from tightly packed RGBA8 texels, like the data a texture was created with.
+    pub fn from_rgba8(width: u32, height: u32, layers: u32, data: &[u8]) -> Self {
+        Self::new(
+            width,
+            height,
+            layers,
+            data.chunks_exact(4).map(|texel| texel[3]).collect(),
+        )
+    }
+
+    /// Returns the alpha of a texel, or 0 outside the texture.
+    pub fn get(&self, x: i32, y: i32, layer: u32) -> u8 {
+        if x < 0 || y < 0 || x as u32 >= self.size.x || y as u32 >= self.size.y {
+            return 0;
+        }
+        if layer >= self.size.z {
+            return 0;
+        }
+        self.alpha[((layer * self.size.y + y as u32) * self.size.x + x as u32) as usize]
+    }
+}
+
+/// Most grid cells an item is indexed in. Bigger items are tested against every point instead.
+const MAX_ITEM_CELLS: i64 = 256;
+
+struct Quad<'a> {
+    group: usize,
+    item: usize,
+    texture: &'a wgpu::Texture,
+    /// Maps target pixels to texels.
+    inverse: Affine2,
+    src_min: Vec2,
+    src_max: Vec2,
+    layer: u32,
+}
+
+/// Finds the items under a point, using the same quads [`crate::Renderer::prepare`] draws.
+///
+/// Items are indexed in a uniform grid when the picker is created, so pick many points between
+/// changes to the groups with one picker.
+pub struct Picker<'a> {
+    cell_size: f32,
+    quads: Vec<Quad<'a>>,
+    grid: HashMap<IVec2, Vec<usize>>,
+    /// Items that cover too many cells to index.
+    oversized: Vec<usize>,
+    alphas: HashMap<&'a wgpu::Texture, &'a TextureAlpha>,
+    alpha_threshold: u8,
+}
+
+impl<'a> Picker<'a> {
+    /// Indexes the items of `groups` as `renderer` would draw them, in grid cells of `cell_size`
+    /// target pixels.
+    ///
+    /// Cells around the size of a typical item work well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cell_size` isn't positive and finite.
+    pub fn new(renderer: &crate::Renderer, groups: &[crate::Group<'a>], cell_size: f32) -> Self {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "cell size must be positive and finite, got {cell_size}"
+        );
+        let mut picker = Self {
+            cell_size,
+            quads: vec![],
+            grid: HashMap::new(),
+            oversized: vec![],
+            alphas: HashMap::new(),
+            alpha_threshold: 0,
+        };
+
+        let tint_color_space = renderer.tint_color_space();
+        for (group_index, group) in groups.iter().enumerate() {
+            let texel_scale = renderer.texel_density(group).recip();
+            for (item_index, item) in group.items.iter().enumerate() {
+                let (instance, _) = renderer.item_instance(item, texel_scale, tint_color_space);
+                let [p0, p1, p2, p3] = instance.corners.map(Vec2::from);
+                let [t_min, t_max] = [
+                    Vec2::from_slice(&instance.tex_rect[..2]),
+                    Vec2::from_slice(&instance.tex_rect[2..]),
+                ];
+
+                // Recover the texel to target pixel transform from the quad, so snapped quads are
+                // picked exactly as drawn.
+                let transform = Affine2::from_mat2_translation(
+                    Mat2::from_cols(
+                        (p2 - p0) / (t_max.x - t_min.x),
+                        (p1 - p0) / (t_max.y - t_min.y),
+                    ),
+                    Vec2::ZERO,
+                );
+                let transform =
+                    Affine2::from_translation(p0 - transform.transform_point2(t_min)) * transform;
+                let inverse = transform.inverse();
+                if !inverse.is_finite() {
+                    continue;
+                }
+
+                let index = picker.quads.len();
+                picker.quads.push(Quad {
+                    group: group_index,
+                    item: item_index,
+                    texture: group.texture,
+                    inverse,
+                    src_min: item.src_offset.as_vec2(),
+                    src_max: item.src_offset.as_vec2() + item.src_size.as_vec2(),
+                    layer: item.src_layer,
+                });
+
+                let min = picker.cell([p0, p1, p2, p3].into_iter().reduce(Vec2::min).unwrap());
+                let max = picker.cell([p0, p1, p2, p3].into_iter().reduce(Vec2::max).unwrap());
+                let cells = (max.as_i64vec2() - min.as_i64vec2() + 1).element_product();
+                if cells > MAX_ITEM_CELLS {
+                    picker.oversized.push(index);
+                    continue;
+                }
+                for y in min.y..=max.y {
+                    for x in min.x..=max.x {
+                        picker.grid.entry(ivec2(x, y)).or_default().push(index);
+                    }
+                }
+            }
+        }
+
+        picker
+    }
+
+    fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Tests points against the alpha of `texture` instead of the whole quad of items drawn with
+    /// it.
+    pub fn set_texture_alpha(&mut self, texture: &'a wgpu::Texture, alpha: &'a TextureAlpha) {
+        self.alphas.insert(texture, alpha);
+    }
+
+    /// Sets the alpha a texel must exceed to be picked, for textures with a [`TextureAlpha`].
+    /// Defaults to 0.
+    pub fn set_alpha_threshold(&mut self, alpha_threshold: u8) {
+        self.alpha_threshold = alpha_threshold;
+    }
+
+    /// Returns the group and item indices of the items at `point`, in target pixels, topmost
+    /// first.
+    pub fn pick(&self, point: Vec2) -> Vec<(usize, usize)> {
+        if !point.is_finite() {
+            return vec![];
+        }
+        let mut candidates = self
+            .grid
+            .get(&self.cell(point))
+            .into_iter()
+            .flatten()
+            .chain(&self.oversized)
+            .copied()
+            .collect::<Vec<_>>();
+
+        // Later items are drawn over earlier ones.
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+        candidates
+            .into_iter()
+            .map(|index| &self.quads[index])
+            .filter(|quad| {
+                let texel = quad.inverse.transform_point2(point);
+                if texel.cmplt(quad.src_min).any() || texel.cmpge(quad.src_max).any() {
+                    return false;
+                }
+                self.alphas.get(quad.texture).is_none_or(|alpha| {
+                    let texel = texel.floor().as_ivec2();
+                    alpha.get(texel.x, texel.y, quad.layer) > self.alpha_threshold
+                })
+            })
+            .map(|quad| (quad.group, quad.item))
+            .collect()
+    }
+}
+
+/// An `R32Uint` texture with the ID of the topmost item at each pixel, rendered with the same
+/// geometry as [`crate::Renderer::render`].
+///
+/// Texels whose alpha, including the tint's, is 0 are discarded, so sprites are picked by their
+/// shape. Groups are sampled with the default shader, even if they have a material. Pixels without
+/// an item are 0; other IDs are resolved with [`crate::PreparedBatch::item_for_id`].
+pub struct IdBuffer {
+    pipeline: wgpu::RenderPipeline,
+    bindless_pipeline: Option<wgpu::RenderPipeline>,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+fn create_id_texture(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("spright: id_buffer"),
+        size: wgpu::Extent3d {
+            width: size.width.max(1),
+            height: size.height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R32Uint,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+impl IdBuffer {
+    /// Creates an ID buffer of the given size for batches prepared by `renderer`.
+    pub fn new(device: &wgpu::Device, renderer: &crate::Renderer, size: wgpu::Extent3d) -> Self {
+        let create_pipeline = |texture_bind_group_layout, shader| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("spright: id_buffer_pipeline_layout"),
+                bind_group_layouts: &[
+                    texture_bind_group_layout,
+                    &renderer.target_uniforms_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("spright: id_buffer_pipeline"),
+                cache: None,
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[crate::Instance::BUFFER_LAYOUT],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_pick"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: wgpu::TextureFormat::R32Uint,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::all(),
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("spright: id_buffer_shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("sprite_bindings.wgsl"),
+                    include_str!("shader.wgsl")
+                )
+                .into(),
+            ),
+        });
+        let pipeline = create_pipeline(&renderer.texture_bind_group_layout, &shader);
+        let bindless_pipeline = renderer
+            .bindless
+            .as_ref()
+            .map(|bindless| create_pipeline(&bindless.bind_group_layout, &bindless.shader));
+
+        let texture = create_id_texture(device, size);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            pipeline,
+            bindless_pipeline,
+            texture,
+            view,
+        }
+    }
+
+    /// Returns the size of the ID buffer.
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.texture.size()
+    }
+
+    /// Reallocates the ID buffer if `size` differs from its current size.
+    pub fn resize(&mut self, device: &wgpu::Device, size: wgpu::Extent3d) {
+        if size == self.texture.size() {
+            return;
+        }
+        self.texture = create_id_texture(device, size);
+        self.view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+    }
+
+    /// Records a render pass that clears the ID buffer and draws the IDs of `batch` into it.
+    ///
+    /// The batch must have been prepared with the size of the ID buffer.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, batch: &crate::PreparedBatch) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("spright: id_buffer_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        rpass.set_vertex_buffer(0, batch.instance_buffer.slice(..));
+        for prepared_group in batch.groups.iter() {
+            rpass.set_bind_group(
+                1,
+                &batch.effects_pages[prepared_group.effects_page].bind_group,
+                &[],
+            );
+            rpass.set_pipeline(match &self.bindless_pipeline {
+                Some(bindless_pipeline) if prepared_group.bindless => bindless_pipeline,
+                _ => &self.pipeline,
+            });
+            rpass.set_bind_group(0, prepared_group.texture_bind_group.as_ref(), &[]);
+            rpass.draw(
+                0..4,
+                prepared_group.instance_start..prepared_group.instance_end,
+            );
+        }
+    }
+
+    /// Records a copy of the ID at `pixel` for reading back. See [`IdBuffer::read_rect`].
+    pub fn read_pixel(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pixel: UVec2,
+    ) -> IdReadback {
+        self.read_rect(device, encoder, pixel, UVec2::ONE)
+    }
+
+    /// Records a copy of the IDs in a rect for reading back, clamped to the ID buffer.
+    ///
+    /// Submit the encoder, then call [`IdReadback::map_async`] and poll the device until it
+    /// completes.
+    pub fn read_rect(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        min: UVec2,
+        size: UVec2,
+    ) -> IdReadback {
+        let texture_size = uvec2(self.texture.width(), self.texture.height());
+        let min = min.min(texture_size);
+        let size = (min + size).min(texture_size) - min;
+
+        let bytes_per_row = (size.x * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("spright: id_readback_buffer"),
+            size: (bytes_per_row * size.y).max(4) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        if size.cmpgt(UVec2::ZERO).all() {
+            encoder.copy_texture_to_buffer(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: min.x,
+                        y: min.y,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        IdReadback {
+            buffer,
+            size,
+            bytes_per_row,
+            mapped: Arc::new(OnceLock::new()),
+        }
+    }
+}
+
+/// IDs copied out of an [`IdBuffer`], readable once mapped.
+pub struct IdReadback {
+    buffer: wgpu::Buffer,
+    size: UVec2,
+    bytes_per_row: u32,
+    mapped: Arc<OnceLock<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+impl IdReadback {
+    /// Starts mapping the IDs for reading, once the commands that copied them have been
+    /// submitted. `callback` is called when the device is polled after the mapping completes.
+    pub fn map_async(
+        &self,
+        callback: impl FnOnce(Result<(), wgpu::BufferAsyncError>) + wgpu::WasmNotSend + 'static,
+    ) {
+        let mapped = self.mapped.clone();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = mapped.set(result.clone());
+                callback(result);
+            });
+    }
+
+    /// Returns whether the IDs have been mapped successfully.
+    pub fn is_ready(&self) -> bool {
+        matches!(self.mapped.get(), Some(Ok(())))
+    }
+
+    fn for_each_id(&self, mut f: impl FnMut(u32)) {
+        assert!(self.is_ready(), "ID readback is not mapped");
+        let data = self.buffer.slice(..).get_mapped_range();
+        for y in 0..self.size.y {
+            let row = &data[(y * self.bytes_per_row) as usize..][..(self.size.x * 4) as usize];
+            for id in row.chunks_exact(4) {
+                f(u32::from_le_bytes(id.try_into().unwrap()));
+            }
+        }
+    }
+
+    /// Returns the ID at the top-left pixel of the rect, or `None` if no item is there.
+    ///
+    /// Panics if the IDs have not been mapped.
+    pub fn id(&self) -> Option<u32> {
+        assert!(self.is_ready(), "ID readback is not mapped");
+        if self.size.cmpeq(UVec2::ZERO).any() {
+            return None;
+        }
+        let data = self.buffer.slice(..).get_mapped_range();
+        Some(u32::from_le_bytes(data[..4].try_into().unwrap())).filter(|&id| id != 0)
+    }
+
+    /// Returns the distinct IDs of the items in the rect.
+    ///
+    /// Panics if the IDs have not been mapped.
+    pub fn ids(&self) -> BTreeSet<u32> {
+        let mut ids = BTreeSet::new();
+        self.for_each_id(|id| {
+            if id != 0 {
+                ids.insert(id);
+            }
+        });
+        ids
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::util::DeviceExt as _;
+
+    use super::*;
+
+    fn texture(device: &wgpu::Device) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    fn item(transform: Affine2) -> crate::Item {
+        crate::Item {
+            src_size: uvec2(4, 4),
+            transform,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn picks_topmost_first() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = texture(&device);
+        let at = |x| item(Affine2::from_translation(vec2(x, 0.0)));
+        let groups = [
+            crate::Group::new(&texture, vec![at(0.0), at(2.0)]),
+            crate::Group::new(&texture, vec![at(1.0)]),
+            // Covers far more cells than are indexed.
+            crate::Group::new(
+                &texture,
+                vec![item(Affine2::from_scale(Vec2::splat(1.0e6)))],
+            ),
+        ];
+
+        let picker = Picker::new(&renderer, &groups, 1.0);
+        assert_eq!(
+            picker.pick(vec2(3.5, 1.0)),
+            [(2, 0), (1, 0), (0, 1), (0, 0)]
+        );
+        assert_eq!(picker.pick(vec2(0.5, 1.0)), [(2, 0), (0, 0)]);
+        assert_eq!(picker.pick(vec2(-0.5, 1.0)), []);
+        assert_eq!(picker.pick(Vec2::NAN), []);
+    }
+
+    #[test]
+    fn picks_rotated_quads_by_shape() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = texture(&device);
+        // A diamond with its top corner at (10, 10).
+        let groups = [crate::Group::new(
+            &texture,
+            vec![item(Affine2::from_angle_translation(
+                std::f32::consts::FRAC_PI_4,
+                vec2(10.0, 10.0),
+            ))],
+        )];
+
+        let picker = Picker::new(&renderer, &groups, 2.0);
+        assert_eq!(picker.pick(vec2(10.0, 12.8)), [(0, 0)]);
+        // Inside the bounding box, outside the diamond.
+        assert_eq!(picker.pick(vec2(12.5, 10.5)), []);
+        assert_eq!(picker.pick(vec2(7.5, 10.5)), []);
+    }
+
+    #[test]
+    fn picks_by_texture_alpha() {
+        let Some((device, _queue)) = crate::test_device() else {
+            return;
+        };
+        let renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = texture(&device);
+        let groups = [crate::Group::new(&texture, vec![item(Affine2::IDENTITY)])];
+        let mut alpha = vec![0xff; 16];
+        alpha[0] = 0;
+        alpha[2] = 128;
+        let alpha = TextureAlpha::new(4, 4, 1, alpha);
+
+        let mut picker = Picker::new(&renderer, &groups, 4.0);
+        assert_eq!(picker.pick(vec2(0.5, 0.5)), [(0, 0)]);
+
+        picker.set_texture_alpha(&texture, &alpha);
+        assert_eq!(picker.pick(vec2(0.5, 0.5)), []);
+        assert_eq!(picker.pick(vec2(1.5, 0.5)), [(0, 0)]);
+        assert_eq!(picker.pick(vec2(2.5, 0.5)), [(0, 0)]);
+
+        picker.set_alpha_threshold(200);
+        assert_eq!(picker.pick(vec2(2.5, 0.5)), []);
+        assert_eq!(picker.pick(vec2(1.5, 0.5)), [(0, 0)]);
+    }
+
+    #[test]
+    fn id_buffer_reads_back_topmost_ids() {
+        let Some((device, queue)) = crate::test_device() else {
+            return;
+        };
+        let renderer = crate::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+        let texture = device.create_texture_with_data(
+            &queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            Default::default(),
+            &[0xff; 4 * 4 * 4],
+        );
+        let at = |x, y| item(Affine2::from_translation(vec2(x, y)));
+        // The second group's item overlaps both of the first group's.
+        let groups = [
+            crate::Group::new(&texture, vec![at(0.0, 0.0), at(4.0, 0.0)]),
+            crate::Group::new(&texture, vec![at(2.0, 2.0)]),
+        ];
+        let size = wgpu::Extent3d {
+            width: 8,
+            height: 8,
+            depth_or_array_layers: 1,
+        };
+        let batch = renderer.prepare(&device, &queue, size, &groups);
+
+        let id_buffer = IdBuffer::new(&device, &renderer, size);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        id_buffer.render(&mut encoder, &batch);
+        let read_pixel =
+            |encoder: &mut _, x, y| id_buffer.read_pixel(&device, encoder, uvec2(x, y));
+        let readbacks = [
+            read_pixel(&mut encoder, 1, 1),
+            read_pixel(&mut encoder, 5, 1),
+            read_pixel(&mut encoder, 3, 3),
+            read_pixel(&mut encoder, 1, 7),
+        ];
+        let rect = id_buffer.read_rect(&device, &mut encoder, UVec2::ZERO, uvec2(8, 8));
+        queue.submit([encoder.finish()]);
+        for readback in readbacks.iter().chain([&rect]) {
+            readback.map_async(|result| result.unwrap());
+        }
+        device.poll(wgpu::Maintain::Wait);
+
+        // IDs are instance indices plus 1, in draw order.
+        let ids = readbacks.map(|readback| readback.id());
+        assert_eq!(ids, [Some(1), Some(2), Some(3), None]);
+        assert_eq!(
+            ids.map(|id| batch.item_for_id(id.unwrap_or(0))),
+            [Some((0, 0)), Some((0, 1)), Some((1, 0)), None]
+        );
+        assert_eq!(rect.ids(), BTreeSet::from([1, 2, 3]));
+    }
+}
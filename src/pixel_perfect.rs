@@ -0,0 +1,114 @@
+//! Pixel-perfect rendering for pixel art.
+
+use glam::*;
+
+/// How transformed quad corners are aligned to the target's pixel grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelSnap {
+    /// Corners are left where the transform puts them.
+    #[default]
+    Off,
+
+    /// Corners are rounded to the nearest pixel edge, so unscaled sprites map texels to pixels
+    /// exactly.
+    Grid,
+
+    /// Corners are rounded to the nearest pixel center, for sprites meant to be centered on a
+    /// pixel, such as odd-sized sprites positioned by their center.
+    HalfPixel,
+}
+
+impl PixelSnap {
+    /// Snaps a point in target pixels.
+    ///
+    /// Halfway points always round up, so a quad keeps its size in pixels wherever it is on the
+    /// target.
+    pub fn snap(self, point: Vec2) -> Vec2 {
+        match self {
+            PixelSnap::Off => point,
+            PixelSnap::Grid => (point + 0.5).floor(),
+            PixelSnap::HalfPixel => point.floor() + 0.5,
+        }
+    }
+}
+
+/// Rounds a scale factor to the nearest nonzero integer, keeping its sign so flips are
+/// preserved.
+///
+/// Scale factors computed in floating point, like `3.0 * 0.1 / 0.1`, can land just below an
+/// integer, so this rounds rather than truncates.
+pub fn integer_scale(scale: f32) -> f32 {
+    let magnitude = (scale.abs() + 0.5).floor().max(1.0);
+    if scale < 0.0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Returns the largest integer scale at which `size` fits in `available`, or 1 if it doesn't
+/// fit at all.
+pub fn integer_scale_to_fit(size: UVec2, available: UVec2) -> u32 {
+    (available / size.max(UVec2::ONE)).min_element().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_snaps_to_nearest_edge_rounding_halves_up() {
+        let snap = |x, y| PixelSnap::Grid.snap(vec2(x, y));
+        assert_eq!(snap(1.2, 1.7), vec2(1.0, 2.0));
+        assert_eq!(snap(1.5, 2.5), vec2(2.0, 3.0));
+        assert_eq!(snap(-1.5, -0.5), vec2(-1.0, 0.0));
+        assert_eq!(snap(1.4999, -1.5001), vec2(1.0, -2.0));
+    }
+
+    #[test]
+    fn half_pixel_snaps_to_pixel_centers() {
+        let snap = |x, y| PixelSnap::HalfPixel.snap(vec2(x, y));
+        assert_eq!(snap(1.2, 1.7), vec2(1.5, 1.5));
+        assert_eq!(snap(1.0, 2.0), vec2(1.5, 2.5));
+        assert_eq!(snap(0.9999, -0.5), vec2(0.5, -0.5));
+        assert_eq!(snap(-1.0, -0.0001), vec2(-0.5, -0.5));
+    }
+
+    #[test]
+    fn off_leaves_points_unchanged() {
+        assert_eq!(PixelSnap::Off.snap(vec2(1.25, -0.5)), vec2(1.25, -0.5));
+    }
+
+    #[test]
+    fn snapped_quads_keep_their_size() {
+        for snap in [PixelSnap::Grid, PixelSnap::HalfPixel] {
+            for x in [0.0, 0.25, 0.5, 0.75, 1.5, -0.5, -2.25] {
+                assert_eq!(
+                    snap.snap(vec2(x + 3.0, 0.0)) - snap.snap(vec2(x, 0.0)),
+                    vec2(3.0, 0.0),
+                    "{snap:?} at {x}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn integer_scale_rounds_to_nonzero_integers() {
+        assert_eq!(integer_scale(3.0 * 0.1 / 0.1), 3.0);
+        assert_eq!(integer_scale(2.4999), 2.0);
+        assert_eq!(integer_scale(2.5), 3.0);
+        assert_eq!(integer_scale(0.2), 1.0);
+        assert_eq!(integer_scale(0.0), 1.0);
+        assert_eq!(integer_scale(-2.5), -3.0);
+        assert_eq!(integer_scale(-0.2), -1.0);
+    }
+
+    #[test]
+    fn integer_scale_to_fit_picks_the_largest_fitting_scale() {
+        assert_eq!(integer_scale_to_fit(uvec2(320, 180), uvec2(1920, 1080)), 6);
+        assert_eq!(integer_scale_to_fit(uvec2(320, 180), uvec2(1919, 1080)), 5);
+        assert_eq!(integer_scale_to_fit(uvec2(320, 180), uvec2(1920, 1079)), 5);
+        assert_eq!(integer_scale_to_fit(uvec2(320, 180), uvec2(100, 100)), 1);
+        assert_eq!(integer_scale_to_fit(uvec2(0, 0), uvec2(3, 5)), 3);
+    }
+}
@@ -0,0 +1,866 @@
+//! Fullscreen post-processing.
+//!
+//! A [`PostProcess`] stack owns an intermediate scene texture for sprites to be rendered into.
+//! After the scene has been drawn, [`PostProcess::render`] runs each [`Effect`] in order and
+//! writes the result to the final target.
+//!
+//! Custom effects are WGSL sources that define a function with the signature:
+//!
+//! ```wgsl
+//! fn effect(in: EffectInput) -> vec4<f32>
+//! ```
+//!
+//! where `EffectInput` is:
+//!
+//! ```wgsl
+//! struct EffectInput {
+//!     // Fragment position, in target pixels.
+//!     position: vec4<f32>,
+//!     // Normalized texture coordinates into `input`.
+//!     uv: vec2<f32>,
+//!     // Sample of `input` at `uv`.
+//!     color: vec4<f32>,
+//! }
+//! ```
+//!
+//! The source may declare a `var<uniform>` block at `@group(1) @binding(0)` if
+//! [`CustomEffectDescriptor::uniforms`] is provided. The previous stage's output (`input`) and a
+//! linear sampler (`input_sampler`) are also in scope.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use encase::{DynamicUniformBuffer, ShaderSize};
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+use crate::uniforms::*;
+
+/// A post-processing effect.
+#[derive(Debug, Clone)]
+pub enum Effect {
+    /// Makes bright areas glow.
+    Bloom {
+        /// Luminance above which pixels contribute to the glow.
+        threshold: f32,
+
+        /// Strength of the glow.
+        intensity: f32,
+
+        /// Blur radius, in half-resolution texels.
+        radius: f32,
+    },
+
+    /// Darkens every other band of rows, like a CRT.
+    Scanlines {
+        /// How much to darken the dark bands, from 0 to 1.
+        intensity: f32,
+
+        /// Height of a light and a dark band together, in pixels.
+        spacing: f32,
+    },
+
+    /// Darkens the edges of the screen.
+    Vignette {
+        /// How much to darken the edges, from 0 to 1.
+        intensity: f32,
+
+        /// Distance from the center where darkening starts, where 1 is the middle of an edge.
+        radius: f32,
+
+        /// Distance over which darkening fades in.
+        softness: f32,
+    },
+
+    /// Adjusts colors.
+    ColorGrade {
+        /// Value added to every channel.
+        brightness: f32,
+
+        /// Contrast multiplier around middle gray.
+        contrast: f32,
+
+        /// Saturation multiplier, where 0 is grayscale.
+        saturation: f32,
+
+        /// Color multiplied into the result.
+        ///
+        /// Like [`crate::Item::tint`], it is converted to the renderer's tint color space, so it
+        /// looks the same regardless of the scene format.
+        tint: crate::LinearColor,
+    },
+
+    /// A user-supplied effect.
+    Custom(CustomEffect),
+}
+
+/// Describes a [`CustomEffect`].
+#[derive(Debug, Clone)]
+pub struct CustomEffectDescriptor<'a> {
+    /// Debug label.
+    pub label: Option<&'a str>,
+
+    /// WGSL source defining the `effect` function.
+    pub source: &'a str,
+
+    /// Initial contents of the uniform block, if any.
+    pub uniforms: Option<&'a [u8]>,
+}
+
+/// A user-supplied post-processing effect.
+///
+/// Custom effects are cheap to clone.
+#[derive(Debug, Clone)]
+pub struct CustomEffect {
+    scene_pipeline: Arc<wgpu::RenderPipeline>,
+    target_pipeline: Arc<wgpu::RenderPipeline>,
+    bind_group: Arc<wgpu::BindGroup>,
+    uniforms_buffer: Option<Arc<wgpu::Buffer>>,
+}
+
+impl CustomEffect {
+    /// Overwrites the contents of the uniform block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the effect was created without uniforms.
+    pub fn write_uniforms(&self, queue: &wgpu::Queue, data: &[u8]) {
+        queue.write_buffer(
+            self.uniforms_buffer
+                .as_ref()
+                .expect("effect has no uniforms"),
+            0,
+            data,
+        );
+    }
+}
+
+/// Custom effect bindings live after the post-processing bind group.
+const CUSTOM_GROUP: u32 = 1;
+
+const SCENE: usize = 0;
+const PING: usize = 1;
+const PONG: usize = 2;
+const BLOOM_A: usize = 3;
+const BLOOM_B: usize = 4;
+
+const BUILT_IN_ENTRY_POINTS: &[&str] = &[
+    "fs_copy",
+    "fs_threshold",
+    "fs_blur",
+    "fs_bloom_composite",
+    "fs_scanlines",
+    "fs_vignette",
+    "fs_color_grade",
+];
+
+#[derive(Clone, Copy)]
+enum PassOutput {
+    Texture(usize),
+    Target,
+}
+
+enum PassPipeline {
+    BuiltIn(&'static str),
+    Custom(CustomEffect),
+}
+
+struct PreparedPass {
+    pipeline: PassPipeline,
+    bind_group: wgpu::BindGroup,
+    output: PassOutput,
+}
+
+struct Textures {
+    size: wgpu::Extent3d,
+    textures: Vec<wgpu::Texture>,
+    views: Vec<wgpu::TextureView>,
+}
+
+/// A stack of fullscreen effects applied to everything rendered into its scene texture.
+pub struct PostProcess {
+    scene_format: wgpu::TextureFormat,
+    target_format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipelines: HashMap<(&'static str, wgpu::TextureFormat), wgpu::RenderPipeline>,
+    sampler: wgpu::Sampler,
+    uniforms_buffer: crate::DynamicBuffer,
+    buffer_capacity: crate::BufferCapacity,
+    tint_color_space: crate::ColorSpace,
+    textures: Option<Textures>,
+    /// Bind groups by pass index, input and secondary input, reused until the textures or the
+    /// uniforms buffer they bind are reallocated.
+    bind_groups: HashMap<(usize, usize, usize), wgpu::BindGroup>,
+    passes: Vec<PreparedPass>,
+
+    /// Effects to apply, in order.
+    pub effects: Vec<Effect>,
+}
+
+fn create_post_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    shader: &wgpu::ShaderModule,
+    fs_entry_point: &str,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        cache: None,
+        layout: Some(
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            }),
+        ),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(fs_entry_point),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::all(),
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+impl PostProcess {
+    /// Creates a new post-processing stack.
+    ///
+    /// Sprites are rendered by `renderer` into an intermediate texture of its texture format. The
+    /// final result is written to a target of `target_format`. Buffers grow and shrink as the
+    /// renderer's [`crate::BufferCapacity`] allows.
+    pub fn new(
+        device: &wgpu::Device,
+        renderer: &crate::Renderer,
+        target_format: wgpu::TextureFormat,
+    ) -> Self {
+        let scene_format = renderer.texture_format();
+        let shader = device.create_shader_module(wgpu::include_wgsl!("postprocess.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: post_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let mut pipelines = HashMap::new();
+        for format in [scene_format, target_format] {
+            for entry_point in BUILT_IN_ENTRY_POINTS {
+                pipelines.entry((*entry_point, format)).or_insert_with(|| {
+                    create_post_pipeline(
+                        device,
+                        "spright: post_pipeline",
+                        &[&bind_group_layout],
+                        &shader,
+                        entry_point,
+                        format,
+                    )
+                });
+            }
+        }
+
+        Self {
+            scene_format,
+            target_format,
+            bind_group_layout,
+            pipelines,
+            sampler: device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }),
+            uniforms_buffer: crate::DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: post_uniforms_buffer"),
+                    size: PostUniforms::SHADER_SIZE.into(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            buffer_capacity: renderer.options().buffer_capacity,
+            tint_color_space: renderer.tint_color_space(),
+            textures: None,
+            bind_groups: HashMap::new(),
+            passes: vec![],
+            effects: vec![],
+        }
+    }
+
+    /// Creates a custom effect.
+    pub fn create_custom_effect(
+        &self,
+        device: &wgpu::Device,
+        desc: &CustomEffectDescriptor,
+    ) -> Result<CustomEffect, crate::ShaderError> {
+        let source = crate::validate_shader(
+            &[
+                include_str!("postprocess.wgsl"),
+                include_str!("postprocess_custom.wgsl"),
+            ]
+            .join("\n"),
+            desc.source,
+            |group, binding| {
+                (group == CUSTOM_GROUP && binding == 0 && desc.uniforms.is_some())
+                    .then_some(crate::ProvidedBinding::Uniform)
+            },
+        )?;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: custom_effect_bind_group_layout"),
+            entries: &desc
+                .uniforms
+                .iter()
+                .map(|_| wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: desc.label,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let uniforms_buffer = desc.uniforms.map(|uniforms| {
+            Arc::new(
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("spright: custom_effect_uniforms_buffer"),
+                    contents: uniforms,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                }),
+            )
+        });
+
+        let create_pipeline = |format| {
+            Arc::new(create_post_pipeline(
+                device,
+                "spright: custom_effect_pipeline",
+                &[&self.bind_group_layout, &bind_group_layout],
+                &shader,
+                "fs_custom",
+                format,
+            ))
+        };
+        let scene_pipeline = create_pipeline(self.scene_format);
+        let target_pipeline = if self.target_format == self.scene_format {
+            scene_pipeline.clone()
+        } else {
+            create_pipeline(self.target_format)
+        };
+
+        Ok(CustomEffect {
+            scene_pipeline,
+            target_pipeline,
+            bind_group: Arc::new(
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("spright: custom_effect_bind_group"),
+                    layout: &bind_group_layout,
+                    entries: &uniforms_buffer
+                        .iter()
+                        .map(|buffer| wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffer.as_entire_binding(),
+                        })
+                        .collect::<Vec<_>>(),
+                }),
+            ),
+            uniforms_buffer,
+        })
+    }
+
+    fn create_textures(&self, device: &wgpu::Device, size: wgpu::Extent3d) -> Textures {
+        let half_size = wgpu::Extent3d {
+            width: (size.width / 2).max(1),
+            height: (size.height / 2).max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let textures = [size, size, size, half_size, half_size]
+            .into_iter()
+            .map(|size| {
+                device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("spright: post_texture"),
+                    size,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.scene_format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Textures {
+            size,
+            views: textures
+                .iter()
+                .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+                .collect(),
+            textures,
+        }
+    }
+
+    /// Prepares the effect stack for a target of the given size.
+    ///
+    /// Intermediate textures are reallocated if the size has changed. Bind groups are reused
+    /// across prepares until then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        target_size: wgpu::Extent3d,
+    ) {
+        let target_size = wgpu::Extent3d {
+            width: target_size.width.max(1),
+            height: target_size.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        if self.textures.as_ref().map(|textures| textures.size) != Some(target_size) {
+            self.textures = Some(self.create_textures(device, target_size));
+            self.bind_groups.clear();
+        }
+        let textures = self.textures.as_ref().unwrap();
+
+        // Plan passes as (pipeline, input, secondary input, output, uniforms).
+        let mut passes = vec![];
+        let mut input = SCENE;
+        for (i, effect) in self.effects.iter().enumerate() {
+            let output = if i == self.effects.len() - 1 {
+                PassOutput::Target
+            } else if input == PING {
+                PassOutput::Texture(PONG)
+            } else {
+                PassOutput::Texture(PING)
+            };
+
+            let params = |params: Vec4| PostUniforms {
+                params,
+                color: Vec4::ONE,
+                texel_size: Vec2::ZERO,
+            };
+
+            match effect {
+                Effect::Bloom {
+                    threshold,
+                    intensity,
+                    radius,
+                } => {
+                    let builtin = |name| PassPipeline::BuiltIn(name);
+                    passes.extend([
+                        (
+                            builtin("fs_threshold"),
+                            input,
+                            input,
+                            PassOutput::Texture(BLOOM_A),
+                            params(vec4(*threshold, 0.0, 0.0, 0.0)),
+                        ),
+                        (
+                            builtin("fs_blur"),
+                            BLOOM_A,
+                            BLOOM_A,
+                            PassOutput::Texture(BLOOM_B),
+                            params(vec4(*radius, 0.0, 0.0, 0.0)),
+                        ),
+                        (
+                            builtin("fs_blur"),
+                            BLOOM_B,
+                            BLOOM_B,
+                            PassOutput::Texture(BLOOM_A),
+                            params(vec4(0.0, *radius, 0.0, 0.0)),
+                        ),
+                        (
+                            builtin("fs_bloom_composite"),
+                            input,
+                            BLOOM_A,
+                            output,
+                            params(vec4(*intensity, 0.0, 0.0, 0.0)),
+                        ),
+                    ]);
+                }
+                Effect::Scanlines { intensity, spacing } => {
+                    passes.push((
+                        PassPipeline::BuiltIn("fs_scanlines"),
+                        input,
+                        input,
+                        output,
+                        params(vec4(*intensity, *spacing, 0.0, 0.0)),
+                    ));
+                }
+                Effect::Vignette {
+                    intensity,
+                    radius,
+                    softness,
+                } => {
+                    passes.push((
+                        PassPipeline::BuiltIn("fs_vignette"),
+                        input,
+                        input,
+                        output,
+                        params(vec4(*intensity, *radius, *softness, 0.0)),
+                    ));
+                }
+                Effect::ColorGrade {
+                    brightness,
+                    contrast,
+                    saturation,
+                    tint,
+                } => {
+                    passes.push((
+                        PassPipeline::BuiltIn("fs_color_grade"),
+                        input,
+                        input,
+                        output,
+                        PostUniforms {
+                            color: Vec4::from_array(crate::tint_to_vec4(
+                                *tint,
+                                self.tint_color_space,
+                            )),
+                            ..params(vec4(*brightness, *contrast, *saturation, 0.0))
+                        },
+                    ));
+                }
+                Effect::Custom(custom_effect) => {
+                    passes.push((
+                        PassPipeline::Custom(custom_effect.clone()),
+                        input,
+                        input,
+                        output,
+                        params(Vec4::ZERO),
+                    ));
+                }
+            }
+
+            if let PassOutput::Texture(output) = output {
+                input = output;
+            }
+        }
+
+        if passes.is_empty() {
+            passes.push((
+                PassPipeline::BuiltIn("fs_copy"),
+                SCENE,
+                SCENE,
+                PassOutput::Target,
+                PostUniforms {
+                    params: Vec4::ZERO,
+                    color: Vec4::ONE,
+                    texel_size: Vec2::ZERO,
+                },
+            ));
+        }
+
+        let uniforms_stride = PostUniforms::SHADER_SIZE
+            .get()
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+
+        let mut uniforms_buffer = DynamicUniformBuffer::new_with_alignment(vec![], uniforms_stride);
+
+        for (_, input, _, _, uniforms) in passes.iter() {
+            let input_size = textures.textures[*input].size();
+            uniforms_buffer
+                .write(&PostUniforms {
+                    texel_size: vec2(
+                        1.0 / input_size.width as f32,
+                        1.0 / input_size.height as f32,
+                    ),
+                    ..*uniforms
+                })
+                .unwrap();
+        }
+
+        if self.uniforms_buffer.write(
+            device,
+            queue,
+            &uniforms_buffer.into_inner(),
+            &self.buffer_capacity,
+        ) {
+            self.bind_groups.clear();
+        }
+
+        // Bind groups of passes that no longer exist are dropped.
+        let mut cached_bind_groups = std::mem::take(&mut self.bind_groups);
+        self.passes = passes
+            .into_iter()
+            .enumerate()
+            .map(|(i, (pipeline, input, secondary, output, _))| {
+                let key = (i, input, secondary);
+                let bind_group = cached_bind_groups.remove(&key).unwrap_or_else(|| {
+                    device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some("spright: post_bind_group"),
+                        layout: &self.bind_group_layout,
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: wgpu::BindingResource::TextureView(
+                                    &textures.views[input],
+                                ),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: wgpu::BindingResource::Sampler(&self.sampler),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 2,
+                                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                                    buffer: &self.uniforms_buffer,
+                                    offset: i as u64 * uniforms_stride,
+                                    size: Some(PostUniforms::SHADER_SIZE),
+                                }),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 3,
+                                resource: wgpu::BindingResource::TextureView(
+                                    &textures.views[secondary],
+                                ),
+                            },
+                        ],
+                    })
+                });
+                self.bind_groups.insert(key, bind_group.clone());
+                PreparedPass {
+                    pipeline,
+                    bind_group,
+                    output,
+                }
+            })
+            .collect();
+    }
+
+    /// Returns the view of the scene texture that sprites should be rendered into.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`PostProcess::prepare`] has not been called.
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self
+            .textures
+            .as_ref()
+            .expect("post-processing stack is not prepared")
+            .views[SCENE]
+    }
+
+    /// Runs the prepared effects over the scene texture and writes the result to `target`.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let Some(textures) = &self.textures else {
+            return;
+        };
+
+        for pass in self.passes.iter() {
+            let (view, load, format) = match pass.output {
+                PassOutput::Texture(i) => (
+                    &textures.views[i],
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    self.scene_format,
+                ),
+                PassOutput::Target => (target, wgpu::LoadOp::Load, self.target_format),
+            };
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("spright: post_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+
+            match &pass.pipeline {
+                PassPipeline::BuiltIn(entry_point) => {
+                    rpass.set_pipeline(&self.pipelines[&(*entry_point, format)]);
+                }
+                PassPipeline::Custom(custom_effect) => {
+                    rpass.set_pipeline(if format == self.scene_format {
+                        &custom_effect.scene_pipeline
+                    } else {
+                        &custom_effect.target_pipeline
+                    });
+                    rpass.set_bind_group(CUSTOM_GROUP, custom_effect.bind_group.as_ref(), &[]);
+                }
+            }
+            rpass.set_bind_group(0, &pass.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: u32, height: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    fn bind_groups(post_process: &PostProcess) -> Vec<wgpu::BindGroup> {
+        post_process
+            .passes
+            .iter()
+            .map(|pass| pass.bind_group.clone())
+            .collect()
+    }
+
+    #[test]
+    fn bind_groups_are_reused_until_resized() {
+        let Some((device, queue)) = crate::test_device() else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = crate::Renderer::new(&device, format);
+        let mut post_process = PostProcess::new(&device, &renderer, format);
+        post_process.effects = vec![
+            Effect::Bloom {
+                threshold: 0.5,
+                intensity: 1.0,
+                radius: 2.0,
+            },
+            Effect::Vignette {
+                intensity: 0.5,
+                radius: 1.0,
+                softness: 0.5,
+            },
+        ];
+
+        post_process.prepare(&device, &queue, size(4, 4));
+        let first = bind_groups(&post_process);
+        post_process.prepare(&device, &queue, size(4, 4));
+        assert_eq!(bind_groups(&post_process), first);
+
+        // The bloom passes keep their inputs and bind groups, and the vignette's is dropped.
+        post_process.effects.pop();
+        post_process.prepare(&device, &queue, size(4, 4));
+        assert_eq!(bind_groups(&post_process), first[..4]);
+        assert_eq!(post_process.bind_groups.len(), 4);
+
+        post_process.prepare(&device, &queue, size(8, 8));
+        let resized = bind_groups(&post_process);
+        assert!(resized.iter().all(|bind_group| !first.contains(bind_group)));
+    }
+
+    #[test]
+    fn resized_scene_is_read_after_prepare() {
+        let Some((device, queue)) = crate::test_device() else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = crate::Renderer::new(&device, format);
+        let mut post_process = PostProcess::new(&device, &renderer, format);
+        let target = crate::render_target::RenderTarget::with_format(&device, format, 8, 8);
+
+        let mut render = |scene_size, color| {
+            post_process.prepare(&device, &queue, scene_size);
+            let mut encoder = device.create_command_encoder(&Default::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: post_process.scene_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            post_process.render(&mut encoder, target.view());
+
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: None,
+                size: 256,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            encoder.copy_texture_to_buffer(
+                target.texture().as_image_copy(),
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(256),
+                        rows_per_image: None,
+                    },
+                },
+                size(1, 1),
+            );
+            queue.submit([encoder.finish()]);
+            buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            let data = buffer.slice(..).get_mapped_range();
+            <[u8; 4]>::try_from(&data[..4]).unwrap()
+        };
+
+        assert_eq!(render(size(4, 4), wgpu::Color::RED), [0xff, 0, 0, 0xff]);
+        assert_eq!(render(size(8, 8), wgpu::Color::GREEN), [0, 0xff, 0, 0xff]);
+    }
+}
@@ -0,0 +1,110 @@
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+struct PostUniforms {
+    params: vec4<f32>,
+    color: vec4<f32>,
+    texel_size: vec2<f32>,
+}
+
+@group(0) @binding(2)
+var<uniform> post_uniforms: PostUniforms;
+@group(0) @binding(3)
+var secondary: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Covers the target with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.uv = uv;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(input, input_sampler, in.uv);
+}
+
+// params.x: threshold
+@fragment
+fn fs_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv);
+    let brightness = luminance(color.rgb);
+    let contribution = max(brightness - post_uniforms.params.x, 0.0) / max(brightness, 1e-4);
+    return vec4<f32>(color.rgb * contribution, 1.0);
+}
+
+// params.xy: blur direction, in texels
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    let offset = post_uniforms.params.xy * post_uniforms.texel_size;
+    var color = textureSample(input, input_sampler, in.uv) * weights[0];
+    for (var i = 1; i < 5; i++) {
+        color += textureSample(input, input_sampler, in.uv + offset * f32(i)) * weights[i];
+        color += textureSample(input, input_sampler, in.uv - offset * f32(i)) * weights[i];
+    }
+    return color;
+}
+
+// params.x: intensity
+@fragment
+fn fs_bloom_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv);
+    let bloom = textureSample(secondary, input_sampler, in.uv);
+    return vec4<f32>(color.rgb + bloom.rgb * post_uniforms.params.x, color.a);
+}
+
+// params.x: intensity, params.y: spacing in pixels
+@fragment
+fn fs_scanlines(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv);
+    let phase = fract(in.position.y / max(post_uniforms.params.y, 1.0));
+    let darken = post_uniforms.params.x * step(0.5, phase);
+    return vec4<f32>(color.rgb * (1.0 - darken), color.a);
+}
+
+// params.x: intensity, params.y: radius, params.z: softness
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv);
+    let falloff = smoothstep(
+        post_uniforms.params.y,
+        post_uniforms.params.y + post_uniforms.params.z,
+        length(in.uv - 0.5) * 2.0,
+    );
+    return vec4<f32>(color.rgb * (1.0 - falloff * post_uniforms.params.x), color.a);
+}
+
+// params.x: brightness, params.y: contrast, params.z: saturation, color: tint
+@fragment
+fn fs_color_grade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input, input_sampler, in.uv);
+    var rgb = color.rgb + post_uniforms.params.x;
+    rgb = (rgb - 0.5) * post_uniforms.params.y + 0.5;
+    rgb = mix(vec3<f32>(luminance(rgb)), rgb, post_uniforms.params.z);
+    rgb *= post_uniforms.color.rgb;
+    return vec4<f32>(max(rgb, vec3<f32>(0.0)), color.a);
+}
+
+struct EffectInput {
+    // Fragment position, in target pixels.
+    position: vec4<f32>,
+    // Normalized texture coordinates into `input`.
+    uv: vec2<f32>,
+    // Sample of `input` at `uv`.
+    color: vec4<f32>,
+}
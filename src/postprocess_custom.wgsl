@@ -0,0 +1,5 @@
+
+@fragment
+fn fs_custom(in: VertexOutput) -> @location(0) vec4<f32> {
+    return effect(EffectInput(in.position, in.uv, textureSample(input, input_sampler, in.uv)));
+}
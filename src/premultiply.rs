@@ -0,0 +1,175 @@
+//! Conversion of textures to premultiplied alpha.
+//!
+//! Textures drawn with [`crate::AlphaMode::Premultiplied`] must have their colors multiplied by
+//! their alpha. Pixel data can be premultiplied on the CPU with [`premultiply_rgba8`] before
+//! upload, or an uploaded texture can be premultiplied on the GPU with a [`Premultiplier`].
+
+use std::collections::HashMap;
+
+/// Premultiplies tightly packed RGBA8 pixel data in place.
+///
+/// Channels are multiplied as stored, so this matches the GPU conversion only for non-sRGB
+/// formats.
+pub fn premultiply_rgba8(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        let a = pixel[3] as u32;
+        for c in &mut pixel[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Premultiplies textures on the GPU.
+pub struct Premultiplier {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl Premultiplier {
+    /// Creates a new premultiplier.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("premultiply.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: premultiply_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("spright: premultiply_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Records commands that write premultiplied copies of every layer of `source` to `target`.
+    ///
+    /// Only mip level 0 is converted; generate mip levels afterwards, e.g. with
+    /// [`crate::mipmap::MipmapGenerator`]. `source` must have been created with
+    /// [`wgpu::TextureUsages::TEXTURE_BINDING`], and `target` with
+    /// [`wgpu::TextureUsages::RENDER_ATTACHMENT`] and the same size and layer count.
+    pub fn premultiply(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::Texture,
+        target: &wgpu::Texture,
+    ) {
+        debug_assert_eq!(source.size(), target.size());
+
+        let format = target.format();
+        let pipeline = self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("spright: premultiply_pipeline"),
+                cache: None,
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &self.shader,
+                    entry_point: Some("fs_premultiply"),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::all(),
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        });
+
+        for layer in 0..source.depth_or_array_layers() {
+            let view = |texture: &wgpu::Texture| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("spright: premultiply_view"),
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_mip_level: 0,
+                    mip_level_count: Some(1),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            };
+            let source_view = view(source);
+            let target_view = view(target);
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("spright: premultiply_bind_group"),
+                layout: &self.bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&source_view),
+                }],
+            });
+
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("spright: premultiply_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, &bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premultiply_rgba8_edge_cases() {
+        let mut data = [
+            [200, 100, 50, 0],    // Transparent colors vanish.
+            [200, 100, 50, 0xff], // Opaque colors are unchanged.
+            [0xff, 128, 0, 128],  // Full channels take the alpha, 64.25 rounds down.
+            [1, 127, 128, 1],     // 0.498 rounds down, 0.502 rounds up.
+            [100, 3, 0xff, 85],   // 33.3 rounds down, 1.0 and 85.0 are exact.
+        ];
+        premultiply_rgba8(data.as_flattened_mut());
+        assert_eq!(
+            data,
+            [
+                [0, 0, 0, 0],
+                [200, 100, 50, 0xff],
+                [128, 64, 0, 128],
+                [0, 0, 1, 1],
+                [33, 1, 85, 85],
+            ]
+        );
+    }
+}
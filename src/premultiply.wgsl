@@ -0,0 +1,15 @@
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+// Covers the target with a single triangle.
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_premultiply(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let color = textureLoad(source, vec2<u32>(position.xy), 0);
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,192 @@
+//! Offscreen textures that can be rendered into and then drawn as sprites.
+
+/// A texture that sprites can be rendered into and that can be used as a [`crate::Group::texture`].
+///
+/// Row 0 of the texture is the top of the rendered image, so drawing it as a sprite has the same
+/// orientation as rendering directly to the screen.
+pub struct RenderTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    multisample: Option<crate::multisample::MultisampleTarget>,
+}
+
+impl RenderTarget {
+    /// Creates a new render target of the given size in the texture format of `renderer`.
+    ///
+    /// If `renderer` multisamples or has a depth/stencil state, the target also owns a
+    /// [`crate::multisample::MultisampleTarget`] that its render passes draw to and resolve from.
+    pub fn new(device: &wgpu::Device, renderer: &crate::Renderer, width: u32, height: u32) -> Self {
+        let mut target = Self::with_format(device, renderer.texture_format(), width, height);
+        let options = renderer.options();
+        if options.sample_count > 1 || options.depth_stencil.is_some() {
+            target.multisample = Some(crate::multisample::MultisampleTarget::new(
+                device,
+                renderer,
+                target.size(),
+            ));
+        }
+        target
+    }
+
+    /// Creates a new render target of the given size and format.
+    ///
+    /// Renderers drawing into the target must be created with the same format, a
+    /// [`crate::RendererOptions::sample_count`] of 1 and no depth/stencil state. Use
+    /// [`RenderTarget::new`] for other renderers.
+    pub fn with_format(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("spright: render_target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            texture,
+            view,
+            multisample: None,
+        }
+    }
+
+    /// Returns the underlying texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// Returns a view of the underlying texture suitable for use as a color attachment.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Returns the size of the target, suitable for passing to [`crate::Renderer::prepare`].
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.texture.size()
+    }
+
+    /// Begins a render pass into the target.
+    ///
+    /// If `clear` is `None`, the previous contents of the target are kept. For multisampled
+    /// targets, these are the samples last drawn through this method.
+    pub fn begin_render_pass<'encoder>(
+        &self,
+        encoder: &'encoder mut wgpu::CommandEncoder,
+        clear: Option<wgpu::Color>,
+    ) -> wgpu::RenderPass<'encoder> {
+        if let Some(multisample) = &self.multisample {
+            return multisample.begin_render_pass(encoder, &self.view, clear);
+        }
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("spright: render_target_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::util::DeviceExt as _;
+
+    #[test]
+    fn multisampled_renderers_draw_into_targets() {
+        let Some((device, queue)) = crate::test_device() else {
+            return;
+        };
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let renderer = crate::Renderer::with_options(
+            &device,
+            format,
+            crate::RendererOptions {
+                sample_count: 4,
+                ..Default::default()
+            },
+        );
+        let size = wgpu::Extent3d {
+            width: 4,
+            height: 4,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture_with_data(
+            &queue,
+            &wgpu::TextureDescriptor {
+                label: None,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            Default::default(),
+            &[0xff; 4 * 4 * 4],
+        );
+
+        let target = RenderTarget::new(&device, &renderer, 4, 4);
+        let batch = renderer.prepare(
+            &device,
+            &queue,
+            target.size(),
+            &[crate::Group::new(
+                &texture,
+                vec![crate::Item {
+                    src_size: glam::uvec2(4, 4),
+                    ..Default::default()
+                }],
+            )],
+        );
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: 256 * 4,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::TRANSPARENT));
+            renderer.render(&mut rpass, &batch);
+        }
+        encoder.copy_texture_to_buffer(
+            target.texture().as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(256),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        queue.submit([encoder.finish()]);
+
+        buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+        let data = buffer.slice(..).get_mapped_range();
+        assert_eq!(data[256 + 4..256 + 8], [0xff; 4]);
+    }
+}
@@ -1,58 +1,270 @@
-@group(0) @binding(0)
-var t: texture_2d_array<f32>;
-@group(0) @binding(1)
-var s: sampler;
+// The group's texture, sampler and uniforms are declared in `sprite_bindings.wgsl`, or
+// `sprite_bindings_bindless.wgsl` for the bindless path, and accessed through the
+// `item_texture_uniforms`, `sample_item_texture`, `sample_item_texture_level` and
+// `load_item_texture` functions declared there.
 
 struct TextureUniforms {
     size: vec3<f32>,
     is_mask: u32,
+    // 0: not indexed, 1: R8Unorm indices, 2: R8Uint indices.
+    indexed: u32,
+    lod_bias: f32,
+    premultiplied: u32,
+    // Bit 0: the texture is bound as an array rather than as a 2D texture, bit 1: the palette is.
+    layered: u32,
 }
 
-@group(0) @binding(2)
-var<uniform> texture_uniforms: TextureUniforms;
-
 struct TargetUniforms {
     size: vec3<f32>,
 }
 
 @group(1) @binding(0)
 var<uniform> target_uniforms: TargetUniforms;
+// Color transforms and effects of the items that have any, `EFFECTS_TEXELS` texels each.
+@group(1) @binding(1)
+var effects_texture: texture_2d<f32>;
+
+// Width of the effects texture and its texels per item, as in lib.rs.
+const EFFECTS_TEXTURE_WIDTH: u32 = 2048u;
+const EFFECTS_TEXELS: u32 = 8u;
+// Effects index of items without a color transform or effects.
+const NO_EFFECTS: u32 = 0xffffffffu;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) layer: u32,
-    @location(3) tint: vec4<f32>,
+// An item, drawn as a triangle strip of 4 vertices.
+struct InstanceInput {
+    // Target positions of the corners.
+    @location(0) corners_01: vec4<f32>,
+    @location(1) corners_23: vec4<f32>,
+    // Texture coordinates of the quad as min.xy, max.xy.
+    @location(2) tex_rect: vec4<f32>,
+    @location(3) src_rect: vec4<f32>,
+    @location(4) tint: vec4<f32>,
+    @location(5) layer: u32,
+    @location(6) palette_row: u32,
+    @location(7) texture_index: u32,
+    @location(8) effects_index: u32,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) layer: u32,
-    @location(2) tint: vec4<f32>,
+    @location(1) tint: vec4<f32>,
+    // Source rect as min.xy, max.xy, in texels.
+    @location(2) @interpolate(flat) src_rect: vec4<f32>,
+    @location(3) @interpolate(flat) layer: u32,
+    @location(4) @interpolate(flat) palette_row: u32,
+    // Index into the texture binding array on the bindless path.
+    @location(5) @interpolate(flat) texture_index: u32,
+    // Index into the effects texture, or `NO_EFFECTS`.
+    @location(6) @interpolate(flat) effects_index: u32,
+    // Item ID for picking, starting at 1.
+    @location(7) @interpolate(flat) id: u32,
 };
 
+// Normalize screen position to NDC position.
+fn to_ndc(position: vec2<f32>) -> vec4<f32> {
+    var pos = (position / target_uniforms.size.xy - 0.5) * 2.0;
+    pos.y = -pos.y;
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(
+    instance: InstanceInput,
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
     var out: VertexOutput;
 
-    out.tint = model.tint;
+    var corners = array(
+        instance.corners_01.xy,
+        instance.corners_01.zw,
+        instance.corners_23.xy,
+        instance.corners_23.zw,
+    );
+    let corner = vec2<f32>(f32(vertex_index >> 1u), f32(vertex_index & 1u));
+    out.tint = instance.tint;
+    out.src_rect = instance.src_rect;
+    out.palette_row = instance.palette_row;
+    out.texture_index = instance.texture_index;
+    out.effects_index = instance.effects_index;
+    out.id = instance_index + 1u;
+    out.tex_coords = mix(instance.tex_rect.xy, instance.tex_rect.zw, corner);
+    out.layer = instance.layer;
+    out.position = to_ndc(corners[vertex_index]);
+    return out;
+}
 
-    // Normalize screen position to NDC position.
-    var pos = (model.position.xy / target_uniforms.size.xy - 0.5) * 2.0;
-    pos.y = -pos.y;
+// Loads texel `i` of the item's effects.
+fn load_effects(in: VertexOutput, i: u32) -> vec4<f32> {
+    let texel = in.effects_index * EFFECTS_TEXELS + i;
+    return textureLoad(
+        effects_texture,
+        vec2<u32>(texel % EFFECTS_TEXTURE_WIDTH, texel / EFFECTS_TEXTURE_WIDTH),
+        0,
+    );
+}
 
-    out.tex_coords = model.tex_coords;
-    out.layer = model.layer;
-    out.position = vec4<f32>(pos, 0.0, 1.0);
-    return out;
+// Indices can't be filtered, so look up the palette at the nearest texel.
+fn palette_lookup(in: VertexOutput, tex_coords: vec2<f32>) -> vec4<f32> {
+    let texture_uniforms = item_texture_uniforms(in);
+    let coords = vec2<u32>(tex_coords);
+    var index: u32;
+    if texture_uniforms.indexed == 1 {
+        index = u32(round(load_item_texture(in, coords).r * 255.0));
+    } else {
+        index = load_index_texture(in, coords);
+    }
+    return load_palette(vec2<u32>(index, in.palette_row));
+}
+
+fn sample_texture(in: VertexOutput) -> vec4<f32> {
+    let texture_uniforms = item_texture_uniforms(in);
+    var sample = sample_item_texture(
+        in,
+        in.tex_coords / texture_uniforms.size.xy,
+        texture_uniforms.lod_bias,
+    );
+    if texture_uniforms.indexed != 0 {
+        sample = palette_lookup(in, in.tex_coords);
+    }
+    if texture_uniforms.is_mask == 1 {
+        if texture_uniforms.premultiplied == 1 {
+            sample = vec4(sample.r);
+        } else {
+            sample = vec4(1.0, 1.0, 1.0, sample.r);
+        }
+    }
+    return sample;
+}
+
+// Tint in the same alpha mode as the texture.
+fn vertex_tint(in: VertexOutput) -> vec4<f32> {
+    let texture_uniforms = item_texture_uniforms(in);
+    if texture_uniforms.premultiplied == 1 {
+        return vec4(in.tint.rgb * in.tint.a, in.tint.a);
+    }
+    return in.tint;
+}
+
+// Applies the color matrix and offset to a tinted color in the texture's alpha mode.
+fn color_transform(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+    let texture_uniforms = item_texture_uniforms(in);
+    var c = color;
+    if texture_uniforms.premultiplied == 1 && c.a > 0.0 {
+        c = vec4(c.rgb / c.a, c.a);
+    }
+    if in.effects_index != NO_EFFECTS {
+        let color_matrix = mat4x4<f32>(
+            load_effects(in, 0u),
+            load_effects(in, 1u),
+            load_effects(in, 2u),
+            load_effects(in, 3u),
+        );
+        // The matrix's translation column and the color offset, added together.
+        c = color_matrix * c + load_effects(in, 4u);
+    }
+    // Keep HDR colors, but alpha must stay in range for blending.
+    c = vec4(max(c.rgb, vec3(0.0)), clamp(c.a, 0.0, 1.0));
+    if texture_uniforms.premultiplied == 1 {
+        c = vec4(c.rgb * c.a, c.a);
+    }
+    return c;
+}
+
+fn in_src_rect(in: VertexOutput, tex_coords: vec2<f32>) -> bool {
+    return all(tex_coords >= in.src_rect.xy) && all(tex_coords < in.src_rect.zw);
+}
+
+// Alpha of the untinted texture at `tex_coords`, or 0 outside the source rect.
+fn texel_alpha(in: VertexOutput, tex_coords: vec2<f32>) -> f32 {
+    let texture_uniforms = item_texture_uniforms(in);
+    if !in_src_rect(in, tex_coords) {
+        return 0.0;
+    }
+    if texture_uniforms.indexed != 0 {
+        return palette_lookup(in, tex_coords).a;
+    }
+    let sample = sample_item_texture_level(in, tex_coords / texture_uniforms.size.xy, 0.0);
+    if texture_uniforms.is_mask == 1 {
+        return sample.r;
+    }
+    return sample.a;
+}
+
+fn premultiply(color: vec4<f32>) -> vec4<f32> {
+    return vec4(color.rgb * color.a, color.a);
+}
+
+// Draws the outline and then the drop shadow under a color in the texture's alpha mode.
+fn apply_effects(in: VertexOutput, color: vec4<f32>) -> vec4<f32> {
+    if in.effects_index == NO_EFFECTS {
+        return color;
+    }
+    let texture_uniforms = item_texture_uniforms(in);
+    let outline_color = load_effects(in, 5u);
+    let shadow_color = load_effects(in, 6u);
+    let params = load_effects(in, 7u);
+    let thickness = params.x;
+    let shadow_offset = params.yz;
+    let blur = params.w;
+    if thickness <= 0.0 && shadow_color.a <= 0.0 {
+        return color;
+    }
+
+    // The quad is expanded past the source rect, where the sprite itself is transparent.
+    var c = select(vec4(0.0), color, in_src_rect(in, in.tex_coords));
+    if texture_uniforms.premultiplied == 0 {
+        c = premultiply(c);
+    }
+
+    if thickness > 0.0 {
+        // Dilate the alpha by sampling two rings around the texel.
+        var alpha = 0.0;
+        for (var i = 0; i < 16; i++) {
+            let angle = f32(i) * (6.2831853 / 16.0);
+            let d = vec2(cos(angle), sin(angle)) * thickness;
+            alpha = max(alpha, texel_alpha(in, in.tex_coords + d));
+            alpha = max(alpha, texel_alpha(in, in.tex_coords + d * 0.5));
+        }
+        c += premultiply(outline_color) * alpha * (1.0 - c.a);
+    }
+
+    if shadow_color.a > 0.0 {
+        let center = in.tex_coords - shadow_offset;
+        var alpha = 0.0;
+        if blur > 0.0 {
+            // 5x5 binomial approximation of a Gaussian spanning the blur radius.
+            let weights = array(1.0, 4.0, 6.0, 4.0, 1.0);
+            for (var y = 0; y < 5; y++) {
+                for (var x = 0; x < 5; x++) {
+                    let d = vec2(f32(x - 2), f32(y - 2)) * (blur * 0.5);
+                    alpha += texel_alpha(in, center + d) * weights[x] * weights[y];
+                }
+            }
+            alpha /= 256.0;
+        } else {
+            alpha = texel_alpha(in, center);
+        }
+        c += premultiply(shadow_color) * alpha * (1.0 - c.a);
+    }
+
+    if texture_uniforms.premultiplied == 0 && c.a > 0.0 {
+        c = vec4(c.rgb / c.a, c.a);
+    }
+    return c;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var sample = textureSample(t, s, in.tex_coords / texture_uniforms.size.xy, in.layer);
-    if texture_uniforms.is_mask == 1 {
-        sample = vec4(1.0, 1.0, 1.0, sample.r);
+    return apply_effects(in, color_transform(in, sample_texture(in) * vertex_tint(in)));
+}
+
+@fragment
+fn fs_pick(in: VertexOutput) -> @location(0) u32 {
+    let alpha = sample_texture(in).a * in.tint.a;
+    if alpha <= 0.0 || !in_src_rect(in, in.tex_coords) {
+        discard;
     }
-    return sample * in.tint;
+    return in.id;
 }
@@ -0,0 +1,636 @@
+//! Top-down 2D lights with shadows cast by occluder polygons and occluder sprites.
+//!
+//! A [`LightMap`] accumulates the ambient light and each light of a [`crate::lighting::Lighting`]
+//! into an offscreen texture, skipping the regions each light's occluders shadow. The light map is
+//! then multiplied over the already rendered scene.
+//!
+//! Polygon shadows are extruded from each [`Occluder`]'s edges. Sprite shadows come from an
+//! occlusion texture, e.g. occluder sprites rendered into a [`crate::render_target::RenderTarget`]:
+//! each light marches rays through its alpha into a 1D shadow map of distances to the nearest
+//! occluder. Shadows are then blurred by the light's [`crate::lighting::Light::size`].
+//!
+//! This is independent of normal-mapped lighting: normal maps and light heights are ignored.
+
+use std::ops::Range;
+
+use encase::{DynamicUniformBuffer, ShaderSize, UniformBuffer};
+use glam::*;
+
+use crate::lighting::{LightKind, Lighting};
+use crate::uniforms::*;
+
+const LIGHT_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const SHADOW_MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+const SHADOW_MAP_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+/// Number of rays in a shadow map.
+const SHADOW_MAP_SIZE: u32 = 1024;
+
+/// How far shadows are extruded away from their light, in pixels.
+const SHADOW_LENGTH: f32 = 1.0e5;
+
+/// A closed polygon that blocks light.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occluder {
+    /// Vertices, in logical pixels like lights. The last vertex connects back to the first.
+    pub points: Vec<Vec2>,
+}
+
+/// A render target and a bind group for reading it in a later pass.
+struct PassTexture {
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+struct Textures {
+    size: wgpu::Extent3d,
+    light_map: PassTexture,
+    shadow_mask: PassTexture,
+    blurred_shadow_mask: PassTexture,
+}
+
+struct PreparedLight {
+    uniforms_offset: u32,
+    shadow_vertices: Range<u32>,
+}
+
+/// Renders lights and shadows into a light map, and multiplies it over a target.
+pub struct LightMap {
+    bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    shadow_pipeline: wgpu::RenderPipeline,
+    shadow_map_pipeline: wgpu::RenderPipeline,
+    sprite_shadow_pipeline: wgpu::RenderPipeline,
+    blur_pipeline: wgpu::RenderPipeline,
+    light_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    shadow_map: PassTexture,
+    occlusion_bind_group: Option<wgpu::BindGroup>,
+    target_uniforms_buffer: wgpu::Buffer,
+    light_uniforms_buffer: crate::DynamicBuffer,
+    vertex_buffer: crate::DynamicBuffer,
+    bind_group: Option<wgpu::BindGroup>,
+    textures: Option<Textures>,
+    ambient: wgpu::Color,
+    lights: Vec<PreparedLight>,
+}
+
+impl LightMap {
+    /// Creates a new light map that is composited onto targets of `target_format`.
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shadows.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: light_map_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: Some(LightUniforms::SHADER_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("spright: light_map_texture_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("spright: light_map_sampler"),
+            // Shadow maps of point lights wrap around at angle 0.
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let create_pipeline = |label,
+                               bind_group_layouts: &[&wgpu::BindGroupLayout],
+                               vs_entry_point,
+                               buffers: &[wgpu::VertexBufferLayout],
+                               fs_entry_point,
+                               target: wgpu::ColorTargetState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                cache: None,
+                layout: Some(
+                    &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: Some(label),
+                        bind_group_layouts,
+                        push_constant_ranges: &[],
+                    }),
+                ),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(vs_entry_point),
+                    buffers,
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(fs_entry_point),
+                    compilation_options: Default::default(),
+                    targets: &[Some(target)],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        // Shadows of different occluders overlap rather than add up.
+        let max = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Max,
+        };
+        let shadow_mask_target = |blend| wgpu::ColorTargetState {
+            format: SHADOW_MASK_FORMAT,
+            blend,
+            write_mask: wgpu::ColorWrites::all(),
+        };
+        let texture_layouts = [&bind_group_layout, &texture_bind_group_layout];
+
+        let shadow_map = create_pass_texture(
+            device,
+            &texture_bind_group_layout,
+            &sampler,
+            "spright: shadow_map",
+            SHADOW_MAP_FORMAT,
+            wgpu::Extent3d {
+                width: SHADOW_MAP_SIZE,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        Self {
+            shadow_pipeline: create_pipeline(
+                "spright: shadow_pipeline",
+                &[&bind_group_layout],
+                "vs_shadow",
+                &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<Vec2>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2],
+                }],
+                "fs_shadow",
+                shadow_mask_target(Some(wgpu::BlendState {
+                    color: max,
+                    alpha: max,
+                })),
+            ),
+            shadow_map_pipeline: create_pipeline(
+                "spright: shadow_map_pipeline",
+                &texture_layouts,
+                "vs_fullscreen",
+                &[],
+                "fs_shadow_map",
+                wgpu::ColorTargetState {
+                    format: SHADOW_MAP_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::all(),
+                },
+            ),
+            sprite_shadow_pipeline: create_pipeline(
+                "spright: sprite_shadow_pipeline",
+                &texture_layouts,
+                "vs_fullscreen",
+                &[],
+                "fs_sprite_shadow",
+                shadow_mask_target(Some(wgpu::BlendState {
+                    color: max,
+                    alpha: max,
+                })),
+            ),
+            blur_pipeline: create_pipeline(
+                "spright: shadow_blur_pipeline",
+                &texture_layouts,
+                "vs_fullscreen",
+                &[],
+                "fs_blur",
+                shadow_mask_target(None),
+            ),
+            light_pipeline: create_pipeline(
+                "spright: light_pipeline",
+                &texture_layouts,
+                "vs_fullscreen",
+                &[],
+                "fs_light",
+                wgpu::ColorTargetState {
+                    format: LIGHT_MAP_FORMAT,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: wgpu::ColorWrites::all(),
+                },
+            ),
+            composite_pipeline: create_pipeline(
+                "spright: light_map_composite_pipeline",
+                &texture_layouts,
+                "vs_fullscreen",
+                &[],
+                "fs_composite",
+                wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Dst,
+                            dst_factor: wgpu::BlendFactor::Zero,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::Zero,
+                            dst_factor: wgpu::BlendFactor::One,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: wgpu::ColorWrites::all(),
+                },
+            ),
+            shadow_map,
+            occlusion_bind_group: None,
+            target_uniforms_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("spright: light_map_target_uniforms_buffer"),
+                size: TargetUniforms::SHADER_SIZE.into(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            light_uniforms_buffer: crate::DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: light_uniforms_buffer"),
+                    size: LightUniforms::SHADER_SIZE.into(),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            vertex_buffer: crate::DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("spright: shadow_vertex_buffer"),
+                    size: std::mem::size_of::<Vec2>() as u64 * 1024,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+            bind_group_layout,
+            texture_bind_group_layout,
+            sampler,
+            bind_group: None,
+            textures: None,
+            ambient: wgpu::Color::WHITE,
+            lights: vec![],
+        }
+    }
+
+    fn create_textures(&self, device: &wgpu::Device, size: wgpu::Extent3d) -> Textures {
+        let create = |label, format| {
+            create_pass_texture(
+                device,
+                &self.texture_bind_group_layout,
+                &self.sampler,
+                label,
+                format,
+                size,
+            )
+        };
+
+        Textures {
+            size,
+            light_map: create("spright: light_map", LIGHT_MAP_FORMAT),
+            shadow_mask: create("spright: shadow_mask", SHADOW_MASK_FORMAT),
+            blurred_shadow_mask: create("spright: blurred_shadow_mask", SHADOW_MASK_FORMAT),
+        }
+    }
+
+    /// Sets a texture whose alpha blocks light, e.g. occluder sprites rendered into a
+    /// [`crate::render_target::RenderTarget`], or `None` to only use polygon occluders.
+    ///
+    /// It must be a filterable color texture with [`wgpu::TextureUsages::TEXTURE_BINDING`], the
+    /// size of the target passed to [`LightMap::prepare`]. Texels with alpha above one half are
+    /// occluders.
+    pub fn set_occlusion(&mut self, device: &wgpu::Device, texture: Option<&wgpu::Texture>) {
+        self.occlusion_bind_group = texture.map(|texture| {
+            create_texture_bind_group(
+                device,
+                &self.texture_bind_group_layout,
+                &self.sampler,
+                &texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            )
+        });
+    }
+
+    /// Prepares lights and the shadows their occluders cast for rendering to a target of the
+    /// given size.
+    ///
+    /// Every occluder, and the texture set with [`LightMap::set_occlusion`], shadows every light.
+    /// Lights inside an occluder are fully shadowed.
+    ///
+    /// Lights and occluders are scaled by `renderer`'s [`crate::Renderer::scale_factor`], so they
+    /// line up with the same lights passed to [`crate::Renderer::prepare_lighting`]. Buffers grow
+    /// and shrink as its [`crate::BufferCapacity`] allows.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        renderer: &crate::Renderer,
+        target_size: wgpu::Extent3d,
+        lighting: &Lighting,
+        occluders: &[Occluder],
+    ) {
+        let target_size = wgpu::Extent3d {
+            width: target_size.width.max(1),
+            height: target_size.height.max(1),
+            depth_or_array_layers: 1,
+        };
+        if self.textures.as_ref().map(|textures| textures.size) != Some(target_size) {
+            self.textures = Some(self.create_textures(device, target_size));
+        }
+
+        queue.write_buffer(&self.target_uniforms_buffer, 0, &{
+            let mut buffer = UniformBuffer::new(vec![]);
+            buffer.write(&crate::target_uniforms(target_size)).unwrap();
+            buffer.into_inner()
+        });
+
+        let color_space = renderer.tint_color_space();
+        let [r, g, b, _] = crate::tint_to_vec4(lighting.ambient, color_space);
+        self.ambient = wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        };
+
+        let stride = LightUniforms::SHADER_SIZE
+            .get()
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+        let mut light_uniforms = DynamicUniformBuffer::new_with_alignment(vec![], stride);
+        let mut vertices = vec![];
+        self.lights.clear();
+
+        let scale_factor = renderer.scale_factor();
+        for light in lighting.lights.iter() {
+            let light = &light.scaled(scale_factor);
+            let uniforms_offset = light_uniforms
+                .write(&crate::lighting::light_uniforms(light, color_space))
+                .unwrap() as u32;
+
+            let extrude = |point: Vec2| {
+                let direction = match light.kind {
+                    LightKind::Directional { direction } => direction.truncate(),
+                    LightKind::Point | LightKind::Spot { .. } => point - light.position,
+                };
+                point + direction.normalize_or_zero() * SHADOW_LENGTH
+            };
+
+            let start = vertices.len() as u32;
+            for occluder in occluders {
+                let points = &occluder.points;
+                for (i, &a) in points.iter().enumerate() {
+                    let a = a * scale_factor;
+                    let b = points[(i + 1) % points.len()] * scale_factor;
+                    let (far_a, far_b) = (extrude(a), extrude(b));
+                    vertices.extend([a, b, far_b, a, far_b, far_a].map(<[f32; 2]>::from));
+                }
+            }
+
+            self.lights.push(PreparedLight {
+                uniforms_offset,
+                shadow_vertices: start..vertices.len() as u32,
+            });
+        }
+
+        if lighting.lights.is_empty() {
+            // Keep the buffer bindable.
+            light_uniforms.write(&LightUniforms::default()).unwrap();
+        }
+
+        let capacity = &renderer.options().buffer_capacity;
+        self.light_uniforms_buffer
+            .write(device, queue, &light_uniforms.into_inner(), capacity);
+        self.vertex_buffer
+            .write(device, queue, bytemuck::cast_slice(&vertices[..]), capacity);
+
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: light_map_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &self.light_uniforms_buffer,
+                        offset: 0,
+                        size: Some(LightUniforms::SHADER_SIZE),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.target_uniforms_buffer.as_entire_binding(),
+                },
+            ],
+        }));
+    }
+
+    /// Renders the light map and multiplies it over `target_view`, which must be the size passed
+    /// to [`LightMap::prepare`].
+    ///
+    /// Does nothing if the light map has not been prepared.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target_view: &wgpu::TextureView) {
+        let (Some(textures), Some(bind_group)) = (&self.textures, &self.bind_group) else {
+            return;
+        };
+
+        // Each light needs its own shadow mask, so gets its own passes. Without lights, a single
+        // pass clears the light map to the ambient light.
+        let passes = self.lights.len().max(1);
+        for i in 0..passes {
+            if let Some(light) = self.lights.get(i) {
+                if let Some(occlusion_bind_group) = &self.occlusion_bind_group {
+                    let mut rpass = begin_pass(
+                        encoder,
+                        "spright: shadow_map_pass",
+                        &self.shadow_map.view,
+                        wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    );
+                    rpass.set_pipeline(&self.shadow_map_pipeline);
+                    rpass.set_bind_group(0, bind_group, &[light.uniforms_offset]);
+                    rpass.set_bind_group(1, occlusion_bind_group, &[]);
+                    rpass.draw(0..3, 0..1);
+                }
+
+                let mut rpass = begin_pass(
+                    encoder,
+                    "spright: shadow_mask_pass",
+                    &textures.shadow_mask.view,
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                );
+                rpass.set_bind_group(0, bind_group, &[light.uniforms_offset]);
+                if !light.shadow_vertices.is_empty() {
+                    rpass.set_pipeline(&self.shadow_pipeline);
+                    rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+                    rpass.draw(light.shadow_vertices.clone(), 0..1);
+                }
+                if self.occlusion_bind_group.is_some() {
+                    rpass.set_pipeline(&self.sprite_shadow_pipeline);
+                    rpass.set_bind_group(1, &self.shadow_map.bind_group, &[]);
+                    rpass.draw(0..3, 0..1);
+                }
+                drop(rpass);
+
+                // The mask is blurred horizontally here, and vertically while adding the light.
+                let mut rpass = begin_pass(
+                    encoder,
+                    "spright: shadow_blur_pass",
+                    &textures.blurred_shadow_mask.view,
+                    wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                );
+                rpass.set_pipeline(&self.blur_pipeline);
+                rpass.set_bind_group(0, bind_group, &[light.uniforms_offset]);
+                rpass.set_bind_group(1, &textures.shadow_mask.bind_group, &[]);
+                rpass.draw(0..3, 0..1);
+            }
+
+            let mut rpass = begin_pass(
+                encoder,
+                "spright: light_pass",
+                &textures.light_map.view,
+                if i == 0 {
+                    wgpu::LoadOp::Clear(self.ambient)
+                } else {
+                    wgpu::LoadOp::Load
+                },
+            );
+            let Some(light) = self.lights.get(i) else {
+                continue;
+            };
+            rpass.set_pipeline(&self.light_pipeline);
+            rpass.set_bind_group(0, bind_group, &[light.uniforms_offset]);
+            rpass.set_bind_group(1, &textures.blurred_shadow_mask.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        let mut rpass = begin_pass(
+            encoder,
+            "spright: light_map_composite_pass",
+            target_view,
+            wgpu::LoadOp::Load,
+        );
+        rpass.set_pipeline(&self.composite_pipeline);
+        rpass.set_bind_group(0, bind_group, &[0]);
+        rpass.set_bind_group(1, &textures.light_map.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn begin_pass<'encoder>(
+    encoder: &'encoder mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPass<'encoder> {
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        ..Default::default()
+    })
+}
+
+fn create_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    view: &wgpu::TextureView,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("spright: light_map_texture_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn create_pass_texture(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    label: &str,
+    format: wgpu::TextureFormat,
+    size: wgpu::Extent3d,
+) -> PassTexture {
+    let view = device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default());
+    PassTexture {
+        bind_group: create_texture_bind_group(device, layout, sampler, &view),
+        view,
+    }
+}
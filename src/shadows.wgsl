@@ -0,0 +1,199 @@
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    color: vec3<f32>,
+    radius: f32,
+    direction: vec3<f32>,
+    falloff: f32,
+    cos_outer: f32,
+    cos_inner: f32,
+    size: f32,
+}
+
+struct TargetUniforms {
+    size: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> light: Light;
+@group(0) @binding(1)
+var<uniform> target_uniforms: TargetUniforms;
+
+// The pass's input: the occlusion texture, shadow map, shadow mask or light map.
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+@group(1) @binding(1)
+var s: sampler;
+
+// Number of rays in a shadow map, as in shadows.rs.
+const SHADOW_MAP_SIZE: f32 = 1024.0;
+
+// Most steps a shadow map ray takes through the occlusion texture.
+const MAX_RAY_STEPS: u32 = 4096u;
+
+// Occlusion texture alpha above which light is blocked.
+const OCCLUSION_THRESHOLD: f32 = 0.5;
+
+// Samples on either side of a pixel when blurring the shadow mask.
+const BLUR_TAPS: i32 = 8;
+
+// Normalize screen position to NDC position.
+fn to_ndc(position: vec2<f32>) -> vec4<f32> {
+    var pos = (position / target_uniforms.size.xy - 0.5) * 2.0;
+    pos.y = -pos.y;
+    return vec4<f32>(pos, 0.0, 1.0);
+}
+
+@vertex
+fn vs_shadow(@location(0) position: vec2<f32>) -> @builtin(position) vec4<f32> {
+    return to_ndc(position);
+}
+
+@fragment
+fn fs_shadow() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+
+// Covers the target with a single triangle.
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+// A ray of the shadow map, which starts at `origin` and travels `length` pixels along
+// `direction`.
+struct Ray {
+    origin: vec2<f32>,
+    direction: vec2<f32>,
+    length: f32,
+}
+
+// Point and spot lights cast a ray per angle. Directional lights cast parallel rays across the
+// target, from outside its far side.
+fn shadow_map_ray(u: f32) -> Ray {
+    if light.kind == 2u {
+        let direction = normalize(light.direction.xy);
+        let across = vec2<f32>(-direction.y, direction.x);
+        let half_diagonal = length(target_uniforms.size.xy) * 0.5;
+        let center = target_uniforms.size.xy * 0.5;
+        return Ray(
+            center - direction * half_diagonal + across * ((u * 2.0 - 1.0) * half_diagonal),
+            direction,
+            half_diagonal * 2.0,
+        );
+    }
+    let angle = u * 6.2831855;
+    return Ray(light.position.xy, vec2<f32>(cos(angle), sin(angle)), max(light.radius, 1e-6));
+}
+
+// Returns the shadow map coordinate and the distance along its ray, as a fraction of the ray's
+// length, of a target position.
+fn shadow_map_coords(position: vec2<f32>) -> vec2<f32> {
+    if light.kind == 2u {
+        let ray = shadow_map_ray(0.5);
+        let across = vec2<f32>(-ray.direction.y, ray.direction.x);
+        let to_position = position - ray.origin;
+        return vec2<f32>(
+            dot(to_position, across) / ray.length + 0.5,
+            dot(to_position, ray.direction) / ray.length,
+        );
+    }
+    let to_position = position - light.position.xy;
+    return vec2<f32>(
+        fract(atan2(to_position.y, to_position.x) / 6.2831855),
+        length(to_position) / max(light.radius, 1e-6),
+    );
+}
+
+// Returns the distances along a ray between which it crosses the target.
+fn target_span(ray: Ray) -> vec2<f32> {
+    // Avoid dividing by zero for axis-aligned rays.
+    let direction = select(ray.direction, vec2<f32>(1e-9), ray.direction == vec2<f32>(0.0));
+    let t0 = -ray.origin / direction;
+    let t1 = (target_uniforms.size.xy - ray.origin) / direction;
+    let near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), 0.0);
+    let far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), ray.length);
+    return vec2<f32>(near, far);
+}
+// Directional lights shining straight down cast no shadows.
+fn casts_shadows() -> bool {
+    return light.kind != 2u || any(light.direction.xy != vec2<f32>(0.0));
+}
+
+// Marches each ray through the occlusion texture, storing the distance to the first occluder as
+// a fraction of the ray's length.
+@fragment
+fn fs_shadow_map(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    if !casts_shadows() {
+        return vec4<f32>(1.0);
+    }
+    let ray = shadow_map_ray(position.x / SHADOW_MAP_SIZE);
+    // Only march where occluders can be, about a pixel at a time.
+    let span = target_span(ray);
+    let steps = min(u32(ceil(max(span.y - span.x, 0.0))), MAX_RAY_STEPS);
+    let step = (span.y - span.x) / f32(steps);
+    let max_texel = vec2<i32>(textureDimensions(t)) - 1;
+    for (var i = 0u; i < steps; i++) {
+        let distance = span.x + (f32(i) + 0.5) * step;
+        let texel = vec2<i32>(floor(ray.origin + ray.direction * distance));
+        if textureLoad(t, clamp(texel, vec2<i32>(0), max_texel), 0).a > OCCLUSION_THRESHOLD {
+            return vec4<f32>(distance / ray.length);
+        }
+    }
+    return vec4<f32>(1.0);
+}
+
+// Shadows the parts of the target beyond the first occluder along their shadow map ray.
+@fragment
+fn fs_sprite_shadow(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    if !casts_shadows() {
+        return vec4<f32>(0.0);
+    }
+    let coords = shadow_map_coords(position.xy);
+    let occluder = textureSampleLevel(t, s, vec2<f32>(coords.x, 0.5), 0.0).r;
+    // Allow a pixel of error, so occluders don't shadow their own edges.
+    let bias = 1.0 / shadow_map_ray(coords.x).length;
+    return vec4<f32>(select(0.0, 1.0, coords.y > occluder + bias));
+}
+
+// Averages the shadow mask along `direction`, spreading shadow edges over the light's size.
+fn blur(position: vec2<f32>, direction: vec2<f32>) -> f32 {
+    let size = vec2<f32>(textureDimensions(t));
+    var sum = 0.0;
+    for (var i = -BLUR_TAPS; i <= BLUR_TAPS; i++) {
+        let offset = direction * light.size * f32(i) / f32(BLUR_TAPS);
+        sum += textureSampleLevel(t, s, (position + offset) / size, 0.0).r;
+    }
+    return sum / f32(BLUR_TAPS * 2 + 1);
+}
+
+@fragment
+fn fs_blur(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return vec4<f32>(blur(position.xy, vec2<f32>(1.0, 0.0)));
+}
+
+// Finishes blurring the shadow mask and adds the light where it isn't shadowed.
+@fragment
+fn fs_light(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    var attenuation = 1.0 - blur(position.xy, vec2<f32>(0.0, 1.0));
+    if light.kind != 2u {
+        let to_light = light.position.xy - position.xy;
+        let dist = length(to_light);
+        attenuation *= pow(saturate(1.0 - dist / max(light.radius, 1e-6)), light.falloff);
+        if light.kind == 1u {
+            let from_light = select(vec2<f32>(0.0), -to_light / dist, dist > 0.0);
+            attenuation *= smoothstep(
+                light.cos_outer,
+                light.cos_inner,
+                dot(from_light, light.direction.xy),
+            );
+        }
+    }
+    return vec4<f32>(light.color * attenuation, 1.0);
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(t, vec2<u32>(position.xy), 0);
+}
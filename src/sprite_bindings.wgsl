@@ -0,0 +1,59 @@
+// Single-layer textures are bound as 2D textures instead of arrays, since some backends can't view
+// them as arrays. `texture_uniforms.layered` picks which binding is used.
+
+@group(0) @binding(0)
+var t: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s: sampler;
+@group(0) @binding(2)
+var<uniform> texture_uniforms: TextureUniforms;
+@group(0) @binding(3)
+var palette: texture_2d<f32>;
+@group(0) @binding(4)
+var t_index: texture_2d_array<u32>;
+@group(0) @binding(5)
+var t_2d: texture_2d<f32>;
+@group(0) @binding(6)
+var t_index_2d: texture_2d<u32>;
+@group(0) @binding(7)
+var palette_array: texture_2d_array<f32>;
+
+fn item_texture_uniforms(in: VertexOutput) -> TextureUniforms {
+    return texture_uniforms;
+}
+
+fn sample_item_texture(in: VertexOutput, coords: vec2<f32>, bias: f32) -> vec4<f32> {
+    if (texture_uniforms.layered & 1) == 0 {
+        return textureSampleBias(t_2d, s, coords, bias);
+    }
+    return textureSampleBias(t, s, coords, in.layer, bias);
+}
+
+fn sample_item_texture_level(in: VertexOutput, coords: vec2<f32>, level: f32) -> vec4<f32> {
+    if (texture_uniforms.layered & 1) == 0 {
+        return textureSampleLevel(t_2d, s, coords, level);
+    }
+    return textureSampleLevel(t, s, coords, in.layer, level);
+}
+
+fn load_item_texture(in: VertexOutput, coords: vec2<u32>) -> vec4<f32> {
+    if (texture_uniforms.layered & 1) == 0 {
+        return textureLoad(t_2d, coords, 0);
+    }
+    return textureLoad(t, coords, in.layer, 0);
+}
+
+fn load_index_texture(in: VertexOutput, coords: vec2<u32>) -> u32 {
+    if (texture_uniforms.layered & 1) == 0 {
+        return textureLoad(t_index_2d, coords, 0).r;
+    }
+    return textureLoad(t_index, coords, in.layer, 0).r;
+}
+
+// Only the first layer of a palette is used.
+fn load_palette(coords: vec2<u32>) -> vec4<f32> {
+    if (texture_uniforms.layered & 2) == 0 {
+        return textureLoad(palette, coords, 0);
+    }
+    return textureLoad(palette_array, coords, 0, 0);
+}
@@ -0,0 +1,33 @@
+// The binding array size is filled in when the shader is created.
+
+@group(0) @binding(0)
+var textures: binding_array<texture_2d_array<f32>, MAX_TEXTURES>;
+@group(0) @binding(1)
+var s: sampler;
+@group(0) @binding(2)
+var<uniform> texture_uniforms: array<TextureUniforms, MAX_TEXTURES>;
+
+fn item_texture_uniforms(in: VertexOutput) -> TextureUniforms {
+    return texture_uniforms[in.texture_index];
+}
+
+fn sample_item_texture(in: VertexOutput, coords: vec2<f32>, bias: f32) -> vec4<f32> {
+    return textureSampleBias(textures[in.texture_index], s, coords, in.layer, bias);
+}
+
+fn sample_item_texture_level(in: VertexOutput, coords: vec2<f32>, level: f32) -> vec4<f32> {
+    return textureSampleLevel(textures[in.texture_index], s, coords, in.layer, level);
+}
+
+fn load_item_texture(in: VertexOutput, coords: vec2<u32>) -> vec4<f32> {
+    return textureLoad(textures[in.texture_index], coords, in.layer, 0);
+}
+
+// Groups with palettes aren't drawn bindless, so these are never called.
+fn load_index_texture(in: VertexOutput, coords: vec2<u32>) -> u32 {
+    return 0u;
+}
+
+fn load_palette(coords: vec2<u32>) -> vec4<f32> {
+    return vec4<f32>(0.0);
+}
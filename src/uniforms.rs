@@ -0,0 +1,94 @@
+//! GPU-side structures shared with the shaders.
+
+// encase's derive emits an unused `check` function per field.
+#![allow(dead_code)]
+
+use encase::ShaderType;
+use glam::*;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct TextureUniforms {
+    pub size: Vec3,
+    pub is_mask: u32,
+    pub indexed: u32,
+    pub lod_bias: f32,
+    pub premultiplied: u32,
+    pub layered: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct TargetUniforms {
+    pub size: Vec3,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct Particle {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+    pub rotation: f32,
+    pub angular_velocity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct ParticleUniforms {
+    pub position: Vec2,
+    pub spread: Vec2,
+    pub velocity: Vec2,
+    pub velocity_spread: Vec2,
+    pub acceleration: Vec2,
+    pub lifetime: Vec2,
+    pub rotation: Vec2,
+    pub angular_velocity: Vec2,
+    pub scale: Vec2,
+    pub start_tint: Vec4,
+    pub end_tint: Vec4,
+    pub src_offset: Vec2,
+    pub src_size: Vec2,
+    pub src_layer: u32,
+    pub emit_count: u32,
+    pub seed: u32,
+    pub dt: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct PostUniforms {
+    pub params: Vec4,
+    pub color: Vec4,
+    pub texel_size: Vec2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct MipmapUniforms {
+    pub tile_size: UVec2,
+    pub premultiplied: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, ShaderType)]
+pub(crate) struct LightUniforms {
+    pub position: Vec3,
+    pub kind: u32,
+    pub color: Vec3,
+    pub radius: f32,
+    pub direction: Vec3,
+    pub falloff: f32,
+    pub cos_outer: f32,
+    pub cos_inner: f32,
+    pub size: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, ShaderType)]
+pub(crate) struct LightingUniforms {
+    pub ambient: Vec3,
+    pub light_count: u32,
+    pub lights: [LightUniforms; crate::lighting::MAX_LIGHTS],
+}
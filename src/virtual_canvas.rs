@@ -0,0 +1,277 @@
+//! Fixed-resolution canvases scaled to fit the window.
+
+use glam::*;
+
+/// How a [`VirtualCanvas`] is scaled to the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasScaling {
+    /// Scale by the largest whole number that fits, so every canvas pixel covers the same number
+    /// of window pixels. Falls back to [`CanvasScaling::Fractional`] if the window is smaller
+    /// than the canvas.
+    Integer,
+
+    /// Scale to fill as much of the window as possible, filtering linearly.
+    Fractional,
+}
+
+/// Where a [`VirtualCanvas`] is drawn in the window, in window pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasViewport {
+    /// Top-left corner.
+    pub offset: Vec2,
+
+    /// Size.
+    pub size: Vec2,
+}
+
+/// A fixed-resolution render target that is scaled up to the window, preserving its aspect ratio
+/// with letterboxing or pillarboxing.
+///
+/// Prepare batches with [`VirtualCanvas::size`] as the target size, render them into
+/// [`VirtualCanvas::target`], then draw the canvas to the window with [`VirtualCanvas::blit`].
+pub struct VirtualCanvas {
+    target: crate::render_target::RenderTarget,
+    scaling: CanvasScaling,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl VirtualCanvas {
+    /// Creates a canvas of the given size in the texture format of `renderer`, to be drawn to
+    /// windows of `window_format`.
+    pub fn new(
+        device: &wgpu::Device,
+        renderer: &crate::Renderer,
+        window_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        scaling: CanvasScaling,
+    ) -> Self {
+        let target = crate::render_target::RenderTarget::new(device, renderer, width, height);
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("virtual_canvas.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("spright: virtual_canvas_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let filter = match scaling {
+            CanvasScaling::Integer => wgpu::FilterMode::Nearest,
+            CanvasScaling::Fractional => wgpu::FilterMode::Linear,
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("spright: virtual_canvas_sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("spright: virtual_canvas_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(target.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("spright: virtual_canvas_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("spright: virtual_canvas_pipeline"),
+            cache: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_blit"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: window_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::all(),
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            target,
+            scaling,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    /// Returns the render target sprites are drawn into.
+    pub fn target(&self) -> &crate::render_target::RenderTarget {
+        &self.target
+    }
+
+    /// Returns the size of the canvas, suitable for passing to [`crate::Renderer::prepare`].
+    pub fn size(&self) -> wgpu::Extent3d {
+        self.target.size()
+    }
+
+    /// Returns where the canvas is drawn in a window of `window_size` pixels.
+    pub fn viewport(&self, window_size: UVec2) -> CanvasViewport {
+        viewport(self.canvas_size(), window_size, self.scaling)
+    }
+
+    /// Maps a cursor position in a window of `window_size` pixels to canvas pixels.
+    ///
+    /// Returns `None` if the position is in the letterbox or pillarbox bars.
+    pub fn window_to_canvas(&self, window_size: UVec2, position: Vec2) -> Option<Vec2> {
+        window_to_canvas(self.canvas_size(), self.viewport(window_size), position)
+    }
+
+    fn canvas_size(&self) -> UVec2 {
+        let size = self.target.size();
+        uvec2(size.width, size.height)
+    }
+
+    /// Records a render pass that draws the canvas to `target`, a window texture of `window_size`
+    /// pixels, filling the bars with `clear`.
+    pub fn blit(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        window_size: UVec2,
+        clear: wgpu::Color,
+    ) {
+        let viewport = self.viewport(window_size);
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("spright: virtual_canvas_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(clear),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        rpass.set_viewport(
+            viewport.offset.x,
+            viewport.offset.y,
+            viewport.size.x,
+            viewport.size.y,
+            0.0,
+            1.0,
+        );
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn viewport(canvas_size: UVec2, window_size: UVec2, scaling: CanvasScaling) -> CanvasViewport {
+    let window_size = window_size.max(UVec2::ONE);
+
+    let fits = canvas_size.cmple(window_size).all();
+    let scale = match scaling {
+        CanvasScaling::Integer if fits => {
+            crate::pixel_perfect::integer_scale_to_fit(canvas_size, window_size) as f32
+        }
+        _ => (window_size.as_vec2() / canvas_size.as_vec2()).min_element(),
+    };
+
+    let scaled_size = canvas_size.as_vec2() * scale;
+    let offset = (window_size.as_vec2() - scaled_size) * 0.5;
+    CanvasViewport {
+        // Keep canvas pixels aligned to window pixels.
+        offset: offset.floor(),
+        size: scaled_size,
+    }
+}
+
+fn window_to_canvas(canvas_size: UVec2, viewport: CanvasViewport, position: Vec2) -> Option<Vec2> {
+    let canvas_size = canvas_size.as_vec2();
+    let canvas = (position - viewport.offset) / viewport.size * canvas_size;
+    (canvas.cmpge(Vec2::ZERO).all() && canvas.cmplt(canvas_size).all()).then_some(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_scaling_letterboxes_with_whole_pixels() {
+        let letterbox = viewport(uvec2(320, 180), uvec2(1000, 800), CanvasScaling::Integer);
+        assert_eq!(letterbox.size, vec2(960.0, 540.0));
+        assert_eq!(letterbox.offset, vec2(20.0, 130.0));
+
+        // Odd bars are floored so canvas pixels stay on window pixels.
+        let letterbox = viewport(uvec2(320, 180), uvec2(641, 361), CanvasScaling::Integer);
+        assert_eq!(letterbox.size, vec2(640.0, 360.0));
+        assert_eq!(letterbox.offset, vec2(0.0, 0.0));
+    }
+
+    #[test]
+    fn integer_scaling_falls_back_to_fractional_in_small_windows() {
+        let letterbox = viewport(uvec2(320, 180), uvec2(160, 180), CanvasScaling::Integer);
+        assert_eq!(letterbox.size, vec2(160.0, 90.0));
+        assert_eq!(letterbox.offset, vec2(0.0, 45.0));
+    }
+
+    #[test]
+    fn fractional_scaling_fills_one_axis() {
+        let letterbox = viewport(uvec2(320, 180), uvec2(1000, 800), CanvasScaling::Fractional);
+        assert_eq!(letterbox.size, vec2(1000.0, 562.5));
+        assert_eq!(letterbox.offset, vec2(0.0, 118.0));
+    }
+
+    #[test]
+    fn window_positions_map_inside_the_viewport_only() {
+        let canvas_size = uvec2(320, 180);
+        let letterbox = viewport(canvas_size, uvec2(1000, 800), CanvasScaling::Integer);
+        let map = |x, y| window_to_canvas(canvas_size, letterbox, vec2(x, y));
+
+        assert_eq!(map(20.0, 130.0), Some(vec2(0.0, 0.0)));
+        assert_eq!(map(500.0, 400.0), Some(vec2(160.0, 90.0)));
+        assert_eq!(map(977.0, 667.0), Some(vec2(319.0, 179.0)));
+
+        // Bars, and the far edges of the viewport.
+        assert_eq!(map(19.9, 400.0), None);
+        assert_eq!(map(500.0, 129.9), None);
+        assert_eq!(map(980.0, 400.0), None);
+        assert_eq!(map(500.0, 670.0), None);
+    }
+}
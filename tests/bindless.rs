@@ -0,0 +1,123 @@
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+const WIDTH: u32 = 8;
+
+/// Returns a device with the features for drawing through a texture binding array, or `None` if
+/// the adapter lacks them.
+fn bindless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let features = wgpu::Features::TEXTURE_BINDING_ARRAY
+        | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING;
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    if !adapter.features().contains(features) {
+        return None;
+    }
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features: features,
+            required_limits: adapter.limits(),
+            ..Default::default()
+        },
+        None,
+    ))
+    .ok()
+}
+
+fn solid_texture(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4]) -> wgpu::Texture {
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &color.repeat(4),
+    )
+}
+
+#[test]
+fn interleaved_textures_draw_from_the_binding_array() {
+    let Some((device, queue)) = bindless_device() else {
+        eprintln!("no adapter with binding arrays available, skipping");
+        return;
+    };
+    let renderer = spright::Renderer::new(&device, wgpu::TextureFormat::Rgba8Unorm);
+    let red = solid_texture(&device, &queue, [0xff, 0, 0, 0xff]);
+    let green = solid_texture(&device, &queue, [0, 0xff, 0, 0xff]);
+
+    let group = |texture, x: f32| {
+        spright::Group::new(
+            texture,
+            vec![spright::Item {
+                src_size: uvec2(2, 2),
+                transform: Affine2::from_translation(vec2(x, 0.0)),
+                ..Default::default()
+            }],
+        )
+    };
+    let target = spright::render_target::RenderTarget::with_format(
+        &device,
+        wgpu::TextureFormat::Rgba8Unorm,
+        WIDTH,
+        2,
+    );
+    let batch = renderer.prepare(
+        &device,
+        &queue,
+        target.size(),
+        &[
+            group(&red, 0.0),
+            group(&green, 2.0),
+            group(&red, 4.0),
+            group(&green, 6.0),
+        ],
+    );
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 256 * 2,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::BLACK));
+        renderer.render(&mut rpass, &batch);
+    }
+    encoder.copy_texture_to_buffer(
+        target.texture().as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(256),
+                rows_per_image: None,
+            },
+        },
+        target.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    for x in 0..WIDTH as usize {
+        let expected = if x / 2 % 2 == 0 {
+            [0xff, 0, 0, 0xff]
+        } else {
+            [0, 0xff, 0, 0xff]
+        };
+        assert_eq!(data[x * 4..x * 4 + 4], expected, "pixel {x}");
+    }
+}
@@ -0,0 +1,255 @@
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+const WIDTH: u32 = 24;
+const HEIGHT: u32 = 8;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    layers: u32,
+    data: &[u8],
+) -> wgpu::Texture {
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &data.repeat(layers as usize),
+    )
+}
+
+/// A 4x4 opaque white square inside a 6x6 transparent frame.
+fn framed_square(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    let data = (0..6)
+        .flat_map(|y| (0..6).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            if (1..5).contains(&x) && (1..5).contains(&y) {
+                [0xff; 4]
+            } else {
+                [0; 4]
+            }
+        })
+        .collect::<Vec<u8>>();
+    create_texture(
+        device,
+        queue,
+        wgpu::TextureFormat::Rgba8Unorm,
+        6,
+        6,
+        1,
+        &data,
+    )
+}
+
+/// An item drawing the square of [`framed_square`] with its top left corner at `x`, 2.
+fn square(x: f32) -> spright::Item {
+    spright::Item {
+        src_offset: ivec2(1, 1),
+        src_size: uvec2(4, 4),
+        transform: Affine2::from_translation(vec2(x, 2.0)),
+        ..Default::default()
+    }
+}
+
+/// Renders `groups` over black and returns the RGBA pixels of the target.
+fn render(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    groups: &[spright::Group<'_>],
+) -> Vec<[u8; 4]> {
+    let renderer = spright::Renderer::new(device, wgpu::TextureFormat::Rgba8Unorm);
+    let target = spright::render_target::RenderTarget::with_format(
+        device,
+        wgpu::TextureFormat::Rgba8Unorm,
+        WIDTH,
+        HEIGHT,
+    );
+    let batch = renderer.prepare(device, queue, target.size(), groups);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 256 * HEIGHT as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::BLACK));
+        renderer.render(&mut rpass, &batch);
+    }
+    encoder.copy_texture_to_buffer(
+        target.texture().as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(256),
+                rows_per_image: None,
+            },
+        },
+        target.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    data.chunks(256)
+        .flat_map(|row| row[..(WIDTH * 4) as usize].chunks(4))
+        .map(|pixel| pixel.try_into().unwrap())
+        .collect()
+}
+
+fn pixel(pixels: &[[u8; 4]], x: u32, y: u32) -> [u8; 4] {
+    pixels[(y * WIDTH + x) as usize]
+}
+
+#[test]
+fn color_transforms_and_outlines_apply_per_item() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+    let texture = framed_square(&device, &queue);
+
+    // Off-target items with effects, so the effects of the visible ones don't start on the first
+    // row of the effects texture.
+    let mut items = (0..600)
+        .map(|i| spright::Item {
+            color_offset: spright::LinearColor::new(0.1, 0.0, 0.0, 0.0),
+            ..square(-100.0 - i as f32)
+        })
+        .collect::<Vec<_>>();
+    items.extend([
+        square(1.0),
+        spright::Item {
+            color_offset: spright::LinearColor::new(-1.0, 0.0, 0.0, 0.0),
+            ..square(8.0)
+        },
+        spright::Item {
+            outline: Some(spright::Outline {
+                color: spright::LinearColor::new(0.0, 0.0, 1.0, 1.0),
+                thickness: 1.0,
+            }),
+            ..square(17.0)
+        },
+    ]);
+
+    let pixels = render(&device, &queue, &[spright::Group::new(&texture, items)]);
+    assert_eq!(pixel(&pixels, 2, 3), [0xff; 4], "untransformed item");
+    assert_eq!(pixel(&pixels, 9, 3), [0, 0xff, 0xff, 0xff], "color offset");
+    assert_eq!(pixel(&pixels, 16, 3), [0, 0, 0xff, 0xff], "outline");
+    assert_eq!(pixel(&pixels, 18, 3), [0xff; 4], "outlined item");
+    assert_eq!(pixel(&pixels, 6, 3), [0, 0, 0, 0xff], "between items");
+}
+
+#[test]
+fn color_matrix_and_drop_shadow() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+    let texture = framed_square(&device, &queue);
+
+    let pixels = render(
+        &device,
+        &queue,
+        &[spright::Group::new(
+            &texture,
+            vec![
+                spright::Item {
+                    tint: spright::LinearColor::new(1.0, 0.0, 0.0, 1.0),
+                    color_matrix: Some(spright::color_matrix::ColorMatrix::grayscale(1.0)),
+                    ..square(1.0)
+                },
+                spright::Item {
+                    drop_shadow: Some(spright::DropShadow {
+                        offset: vec2(2.0, 0.0),
+                        blur: 0.0,
+                        color: spright::LinearColor::new(0.0, 1.0, 0.0, 1.0),
+                    }),
+                    ..square(8.0)
+                },
+            ],
+        )],
+    );
+
+    let [r, g, b, a] = pixel(&pixels, 2, 3);
+    assert!(
+        r == g && g == b && r > 0 && r < 0xff && a == 0xff,
+        "grayscale is {:?}",
+        [r, g, b, a]
+    );
+    assert_eq!(pixel(&pixels, 11, 3), [0xff; 4], "shadowed item");
+    assert_eq!(pixel(&pixels, 13, 3), [0, 0xff, 0, 0xff], "drop shadow");
+}
+
+#[test]
+fn palette_rows_recolor_items() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+    let indices = create_texture(
+        &device,
+        &queue,
+        wgpu::TextureFormat::R8Uint,
+        4,
+        4,
+        1,
+        &[1; 16],
+    );
+    // Only the first layer of a palette is used.
+    let palette = create_texture(
+        &device,
+        &queue,
+        wgpu::TextureFormat::Rgba8Unorm,
+        2,
+        2,
+        2,
+        &[
+            0, 0, 0, 0xff, 0xff, 0, 0, 0xff, //
+            0, 0, 0, 0xff, 0, 0xff, 0, 0xff,
+        ],
+    );
+
+    let item = |x: f32, palette_row| spright::Item {
+        src_size: uvec2(4, 4),
+        transform: Affine2::from_translation(vec2(x, 2.0)),
+        palette_row,
+        ..Default::default()
+    };
+    let pixels = render(
+        &device,
+        &queue,
+        &[spright::Group {
+            palette: Some(&palette),
+            ..spright::Group::new(&indices, vec![item(1.0, 0), item(8.0, 1)])
+        }],
+    );
+    assert_eq!(pixel(&pixels, 2, 3), [0xff, 0, 0, 0xff], "row 0");
+    assert_eq!(pixel(&pixels, 9, 3), [0, 0xff, 0, 0xff], "row 1");
+}
@@ -0,0 +1,136 @@
+use glam::*;
+use spright::lighting::{Light, LightKind, Lighting};
+use wgpu::util::DeviceExt as _;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+fn create_texture(device: &wgpu::Device, queue: &wgpu::Queue, texel: [u8; 4]) -> wgpu::Texture {
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 2,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &texel.repeat(4),
+    )
+}
+
+/// Renders a white sprite with a flat normal map, lit by `lighting`, and returns the first pixel
+/// of the target.
+fn render_lit(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    lighting: &Lighting,
+) -> [u8; 4] {
+    let renderer = spright::Renderer::new(device, format);
+    let texture = create_texture(device, queue, [0xff; 4]);
+    let normal_map = create_texture(device, queue, [0x80, 0x80, 0xff, 0xff]);
+
+    let target = spright::render_target::RenderTarget::with_format(device, format, 2, 2);
+    let batch = renderer.prepare(
+        device,
+        queue,
+        target.size(),
+        &[spright::Group {
+            normal_map: Some(&normal_map),
+            ..spright::Group::new(
+                &texture,
+                vec![spright::Item {
+                    src_size: uvec2(2, 2),
+                    ..Default::default()
+                }],
+            )
+        }],
+    );
+    renderer.prepare_lighting(queue, &batch, lighting);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 256 * 2,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::TRANSPARENT));
+        renderer.render(&mut rpass, &batch);
+    }
+    encoder.copy_texture_to_buffer(
+        target.texture().as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(256),
+                rows_per_image: None,
+            },
+        },
+        target.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    data[..4].try_into().unwrap()
+}
+
+#[test]
+fn light_colors_match_across_target_formats() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    // Linear 0.5 is sRGB 188.
+    let gray = spright::LinearColor::new(0.5, 0.5, 0.5, 1.0);
+    let ambient = Lighting {
+        ambient: gray,
+        lights: vec![],
+    };
+    let light = Lighting {
+        ambient: spright::LinearColor::new(0.0, 0.0, 0.0, 1.0),
+        lights: vec![Light {
+            kind: LightKind::Directional {
+                direction: vec3(0.0, 0.0, -1.0),
+            },
+            position: Vec2::ZERO,
+            height: 0.0,
+            color: gray,
+            intensity: 1.0,
+            radius: 0.0,
+            falloff: 1.0,
+            size: 0.0,
+        }],
+    };
+
+    for format in [
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ] {
+        for (name, lighting) in [("ambient", &ambient), ("light", &light)] {
+            let pixel = render_lit(&device, &queue, format, lighting);
+            assert!(
+                pixel[..3].iter().all(|c| c.abs_diff(188) <= 2) && pixel[3] == 0xff,
+                "{format:?}, {name}: rendered {pixel:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use spright::premultiply::{premultiply_rgba8, Premultiplier};
+use wgpu::util::DeviceExt as _;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+/// Premultiplies a 2x2 texture of `texels` on the GPU and returns the converted texels.
+fn premultiply(device: &wgpu::Device, queue: &wgpu::Queue, texels: &[u8]) -> Vec<u8> {
+    let size = wgpu::Extent3d {
+        width: 2,
+        height: 2,
+        depth_or_array_layers: 1,
+    };
+    let descriptor = wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    };
+    let source = device.create_texture_with_data(queue, &descriptor, Default::default(), texels);
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        ..descriptor
+    });
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 256 * 2,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    Premultiplier::new(device).premultiply(device, &mut encoder, &source, &target);
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(256),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    [&data[..8], &data[256..][..8]].concat()
+}
+
+#[test]
+fn gpu_premultiply_matches_cpu() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let texels = [
+        200, 100, 50, 0, //
+        200, 100, 50, 0xff, //
+        0xff, 0xff, 0, 128, //
+        1, 127, 128, 1,
+    ];
+    let mut expected = texels;
+    premultiply_rgba8(&mut expected);
+
+    let actual = premultiply(&device, &queue, &texels);
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            actual.abs_diff(expected) <= 1,
+            "premultiplied {actual:?}, expected {expected:?}"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use glam::*;
+use spright::lighting::{Light, LightKind, Lighting};
+use spright::shadows::{LightMap, Occluder};
+use wgpu::util::DeviceExt as _;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 8;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+fn light(kind: LightKind, size: f32) -> Light {
+    Light {
+        kind,
+        position: vec2(4.0, 4.0),
+        height: 0.0,
+        color: spright::LinearColor::WHITE,
+        intensity: 1.0,
+        radius: 1.0e4,
+        falloff: 1.0,
+        size,
+    }
+}
+
+/// A wall across the target from x = 20 to 22.
+fn wall() -> Occluder {
+    Occluder {
+        points: vec![
+            vec2(20.0, -1.0),
+            vec2(22.0, -1.0),
+            vec2(22.0, HEIGHT as f32 + 1.0),
+            vec2(20.0, HEIGHT as f32 + 1.0),
+        ],
+    }
+}
+
+/// The same wall as occluder sprite alpha.
+fn wall_occlusion(device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::Texture {
+    let data = (0..HEIGHT)
+        .flat_map(|_| 0..WIDTH)
+        .flat_map(|x| [0, 0, 0, if (20..22).contains(&x) { 0xff } else { 0 }])
+        .collect::<Vec<u8>>();
+    device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &data,
+    )
+}
+
+/// Multiplies the light map over a white target with no ambient light and returns the red
+/// channel of its middle row.
+fn render_light(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    light: Light,
+    occluders: &[Occluder],
+    occlusion: Option<&wgpu::Texture>,
+) -> Vec<u8> {
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let renderer = spright::Renderer::new(device, format);
+    let size = wgpu::Extent3d {
+        width: WIDTH,
+        height: HEIGHT,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &vec![0xff; (WIDTH * HEIGHT * 4) as usize],
+    );
+
+    let mut light_map = LightMap::new(device, format);
+    light_map.set_occlusion(device, occlusion);
+    light_map.prepare(
+        device,
+        queue,
+        &renderer,
+        size,
+        &Lighting {
+            ambient: spright::LinearColor::new(0.0, 0.0, 0.0, 1.0),
+            lights: vec![light],
+        },
+        occluders,
+    );
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (WIDTH * 4 * HEIGHT) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    light_map.render(
+        &mut encoder,
+        &target.create_view(&wgpu::TextureViewDescriptor::default()),
+    );
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(WIDTH * 4),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    let row = (HEIGHT / 2 * WIDTH * 4) as usize;
+    data[row..row + (WIDTH * 4) as usize]
+        .chunks(4)
+        .map(|pixel| pixel[0])
+        .collect()
+}
+
+#[test]
+fn occluders_cast_hard_shadows() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+    let occlusion = wall_occlusion(&device, &queue);
+
+    for kind in [
+        LightKind::Point,
+        LightKind::Directional {
+            direction: vec3(1.0, 0.0, -1.0),
+        },
+    ] {
+        for (occluders, occlusion) in [(&[wall()][..], None), (&[][..], Some(&occlusion))] {
+            let row = render_light(&device, &queue, light(kind, 0.0), occluders, occlusion);
+            assert!(row[10] > 250, "{kind:?}: lit side is {row:?}");
+            assert_eq!(row[40], 0, "{kind:?}: shadowed side is {row:?}");
+        }
+    }
+}
+
+#[test]
+fn bigger_lights_cast_softer_shadows() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let hard = render_light(
+        &device,
+        &queue,
+        light(LightKind::Point, 0.0),
+        &[wall()],
+        None,
+    );
+    let soft = render_light(
+        &device,
+        &queue,
+        light(LightKind::Point, 6.0),
+        &[wall()],
+        None,
+    );
+    assert_eq!(hard[23], 0, "hard shadow is {hard:?}");
+    assert!(
+        (1..255).contains(&soft[23]) && (1..255).contains(&soft[18]),
+        "soft shadow is {soft:?}"
+    );
+    assert_eq!(soft[40], 0, "soft shadow is {soft:?}");
+}
+
+#[test]
+fn zero_radius_lights_add_nothing() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let row = render_light(
+        &device,
+        &queue,
+        Light {
+            radius: 0.0,
+            ..light(LightKind::Point, 0.0)
+        },
+        &[],
+        None,
+    );
+    assert_eq!(row[10], 0, "{row:?}");
+}
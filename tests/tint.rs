@@ -0,0 +1,157 @@
+use glam::*;
+use wgpu::util::DeviceExt as _;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+    pollster::block_on(adapter.request_device(&Default::default(), None)).ok()
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Renders a white sprite with `tint` and returns the first pixel of the target.
+fn render_tinted(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture_format: wgpu::TextureFormat,
+    format: wgpu::TextureFormat,
+    tint: spright::LinearColor,
+) -> [f32; 4] {
+    let renderer = spright::Renderer::new(device, format);
+
+    let size = wgpu::Extent3d {
+        width: 2,
+        height: 2,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture_with_data(
+        queue,
+        &wgpu::TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture_format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        Default::default(),
+        &match texture_format {
+            wgpu::TextureFormat::Rgba16Float => 0x3c00u16.to_le_bytes().repeat(2 * 2 * 4),
+            _ => vec![0xff; 2 * 2 * 4],
+        },
+    );
+
+    let target = spright::render_target::RenderTarget::with_format(device, format, 2, 2);
+    let batch = renderer.prepare(
+        device,
+        queue,
+        target.size(),
+        &[spright::Group::new(
+            &texture,
+            vec![spright::Item {
+                src_size: uvec2(2, 2),
+                tint,
+                ..Default::default()
+            }],
+        )],
+    );
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 256 * 2,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut rpass = target.begin_render_pass(&mut encoder, Some(wgpu::Color::TRANSPARENT));
+        renderer.render(&mut rpass, &batch);
+    }
+    encoder.copy_texture_to_buffer(
+        target.texture().as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(256),
+                rows_per_image: None,
+            },
+        },
+        target.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+    let data = buffer.slice(..).get_mapped_range();
+    std::array::from_fn(|i| match format {
+        wgpu::TextureFormat::Rgba16Float => {
+            f16_to_f32(u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]))
+        }
+        _ => data[i] as f32 / 255.0,
+    })
+}
+
+#[test]
+fn tint_matches_across_target_formats() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    // The texture is white, which is the same in either encoding, so only the target's encoding
+    // should affect the result.
+    let tint = spright::Color::new(128, 64, 200, 255);
+    for texture_format in [
+        wgpu::TextureFormat::Rgba8Unorm,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ] {
+        for format in [
+            wgpu::TextureFormat::Rgba8Unorm,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        ] {
+            let pixel = render_tinted(&device, &queue, texture_format, format, tint.into());
+            let pixel = pixel.map(|c| (c * 255.0).round() as u8);
+            for (actual, expected) in pixel.iter().zip([tint.r, tint.g, tint.b, tint.a]) {
+                assert!(
+                    actual.abs_diff(expected) <= 1,
+                    "{texture_format:?} on {format:?}: rendered {pixel:?}, expected {tint:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn hdr_tint_on_float_target() {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter available, skipping");
+        return;
+    };
+
+    let tint = spright::LinearColor::new(4.0, 2.0, 0.5, 1.0);
+    let pixel = render_tinted(
+        &device,
+        &queue,
+        wgpu::TextureFormat::Rgba16Float,
+        wgpu::TextureFormat::Rgba16Float,
+        tint,
+    );
+    for (actual, expected) in pixel.iter().zip([tint.r, tint.g, tint.b, tint.a]) {
+        assert!(
+            (actual - expected).abs() <= 0.01,
+            "rendered {pixel:?}, expected {tint:?}"
+        );
+    }
+}